@@ -0,0 +1,400 @@
+//! Offline disassembler, the library counterpart of `disassembler-server`.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use clap::Parser;
+use serde::Deserialize;
+use serde_with::{json::JsonString, serde_as};
+
+use disassembler::{
+    ArchOptions, AssemblerOutput, BitWidth, DisassemblerRegistry, Discovery, ErrorDetails, Image,
+    Input, Invalid, Pattern, PatternError, Region, Resync, ShowAddress, SymbolFile, SymbolFormat,
+    SymbolInfo, Truncated,
+};
+
+/// Disassembles a file or standard input.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Target architecture, e.g. `mos6502` or `x86`, detected from an executable input.
+    #[arg(long, short)]
+    arch: Option<String>,
+    /// Input file format: `raw`, `elf`, `pe`, `prg`, `t64`, `d64`, `nes`,
+    /// `hex` or `srec`.
+    #[arg(long, value_parser = parse_input, default_value = "raw")]
+    input: Input,
+    /// Program to load from a `t64` or `d64` image, the first one if omitted.
+    #[arg(long)]
+    member: Option<String>,
+    /// Target architecture bit width.
+    #[arg(long, short, value_parser = parse_width)]
+    width: Option<BitWidth>,
+    /// Output disassembly syntax, e.g. `intel` or `att`.
+    #[arg(long, short)]
+    syntax: Option<String>,
+    /// Address of the first byte, e.g. `0xC000`, `$C000` or `49152`.
+    #[arg(long, short, value_parser = parse_number, default_value = "0")]
+    origin: usize,
+    /// Do not show addresses.
+    #[arg(long, conflicts_with = "origin")]
+    no_address: bool,
+    /// Address to stop at.
+    #[arg(long, value_parser = parse_number)]
+    stop: Option<usize>,
+    /// Show instructions in the lower case.
+    #[arg(long)]
+    lower_case: bool,
+    /// Show how many cycles does the instruction take.
+    #[arg(long)]
+    cycles: bool,
+    /// How to show a truncated trailing instruction: `data`, `comment` or `error`.
+    #[arg(long, value_parser = parse_truncated, default_value = "data")]
+    truncated: Truncated,
+    /// How to show invalid instructions: `raw`, `data`, `placeholder` or `error`.
+    #[arg(long, value_parser = parse_invalid, default_value = "raw")]
+    invalid: Invalid,
+    /// Where to continue after an invalid instruction: `length` or `byte`.
+    #[arg(long, value_parser = parse_resync, default_value = "length")]
+    resync: Resync,
+    /// Symbol table file, a `.json` one in the format of the service
+    /// or a tool-native one, e.g. a VICE label file.
+    #[arg(long)]
+    symbols: Option<PathBuf>,
+    /// Tool-native symbol file format, detected if omitted:
+    /// `vice`, `ld65-dbg`, `ld65-map`, `fceux`, `mesen` or `assignments`.
+    #[arg(long, value_parser = parse_symbol_format, requires = "symbols")]
+    symbols_format: Option<SymbolFormat>,
+    /// Decode only code reachable from entry points, the rest is data.
+    #[arg(long)]
+    recursive: bool,
+    /// Entry point address for `--recursive` and `--functions`, may be repeated.
+    #[arg(long, value_parser = parse_number)]
+    entry: Vec<usize>,
+    /// Show cross references.
+    #[arg(long)]
+    xrefs: bool,
+    /// Print the control flow graph of the function at this address instead.
+    #[arg(long, value_parser = parse_number)]
+    cfg: Option<usize>,
+    /// Print functions and the call graph as JSON instead.
+    #[arg(long, conflicts_with = "cfg")]
+    functions: bool,
+    /// Print the ranked architectures the input may be code of as JSON instead.
+    #[arg(long, conflicts_with_all = ["cfg", "functions"])]
+    detect: bool,
+    /// Print a unified diff of the instructions against this patched version instead.
+    #[arg(long, conflicts_with_all = ["cfg", "functions", "detect"])]
+    diff: Option<PathBuf>,
+    /// Print the instruction sequences matching this pattern instead, e.g. `LDA #*; STA $D4??`.
+    #[arg(long, conflicts_with_all = ["cfg", "functions", "detect", "diff"])]
+    search: Option<String>,
+    /// Instructions shown before and after each `--search` match.
+    #[arg(long, default_value = "0", requires = "search")]
+    context: usize,
+    /// Control flow graph format: `dot` or `json`.
+    #[arg(long, value_parser = parse_graph, default_value = "dot", requires = "cfg")]
+    graph: Graph,
+    /// Data regions file, a JSON array in the format of the service.
+    #[arg(long)]
+    regions: Option<PathBuf>,
+    /// Input file, standard input if omitted or `-`.
+    file: Option<PathBuf>,
+}
+
+/// Output format of `--cfg`.
+#[derive(Debug, Clone, Copy)]
+enum Graph {
+    Dot,
+    Json,
+}
+
+/// A symbol table as it is sent to the service.
+#[serde_as]
+#[derive(Deserialize)]
+struct SymbolTable(#[serde_as(as = "HashMap<JsonString, _>")] HashMap<SymbolInfo, String>);
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    match run(args) {
+        Ok(lines) => {
+            for line in lines {
+                println!("{line}");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Args) -> Result<Vec<String>, String> {
+    let bytes = match &args.file {
+        Some(path) if path.as_os_str() != "-" => {
+            fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?
+        }
+        _ => {
+            let mut bytes = vec![];
+            io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(|e| format!("stdin: {e}"))?;
+            bytes
+        }
+    };
+
+    if args.detect {
+        let candidates = DisassemblerRegistry::builtin().detect(&bytes);
+        return Ok(vec![
+            serde_json::to_string_pretty(&candidates).expect("candidates are serializable")
+        ]);
+    }
+
+    let mut arch = ArchOptions::default();
+    if let Some(name) = &args.arch {
+        arch = arch.with_arch(name);
+    }
+    if let Some(width) = args.width {
+        arch = arch.with_width(width);
+    }
+    if let Some(syntax) = &args.syntax {
+        arch = arch.with_syntax(syntax);
+    }
+
+    let address = if args.no_address {
+        ShowAddress::None
+    } else {
+        ShowAddress::Start(args.origin)
+    };
+    let mut format = AssemblerOutput::default()
+        .with_addresses(address)
+        .with_upper_case(!args.lower_case)
+        .with_cycles(args.cycles)
+        .with_truncated(args.truncated)
+        .with_invalid(args.invalid)
+        .with_resync(args.resync)
+        .with_xrefs(args.xrefs);
+    if let Some(stop) = args.stop {
+        format = format.with_stop(stop);
+    }
+    if let Some(path) = &args.symbols {
+        let file = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        format = if path.extension().is_some_and(|ext| ext == "json") {
+            let SymbolTable(table) =
+                serde_json::from_str(&file).map_err(|e| format!("{}: {e}", path.display()))?;
+            format.with_symbol_table(table)
+        } else {
+            let file = SymbolFile {
+                format: args.symbols_format,
+                text: file,
+            };
+            format
+                .with_symbol_file(&file)
+                .map_err(|e| format!("{}: {e}", path.display()))?
+        };
+    }
+
+    if let Some(path) = &args.regions {
+        let file = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let regions: Vec<Region> =
+            serde_json::from_str(&file).map_err(|e| format!("{}: {e}", path.display()))?;
+        format = format.with_regions(regions);
+    }
+
+    if args.recursive {
+        format = format.with_discovery(Discovery::Recursive(args.entry.clone()));
+    }
+
+    let input = args.input.clone().with_file(args.member.clone());
+    let image = input.load(&bytes).map_err(|e| e.to_string())?;
+    let patched = match &args.diff {
+        Some(path) => {
+            let bytes = fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            let image = input
+                .load(&bytes)
+                .map_err(|e| format!("{}: {e}", path.display()))?;
+            Some((bytes, image))
+        }
+        None => None,
+    };
+    if let Some(image) = &image {
+        arch = arch.or(&image.arch);
+    }
+    let pattern: Option<Pattern> = args
+        .search
+        .as_deref()
+        .map(str::parse)
+        .transpose()
+        .map_err(|e: PatternError| e.to_string())?;
+    let name = arch
+        .arch()
+        .ok_or("the architecture is not specified, use `--arch`")?;
+
+    let registry = DisassemblerRegistry::builtin();
+    registry
+        .get(name, &arch)
+        .and_then(|disasm| {
+            let decode_image = |bytes: &[u8], image: &Option<Image>| match image {
+                Some(image) => image.decode(&*disasm, &format),
+                None => disasm.decode_dyn(bytes, &format),
+            };
+            let decode = || decode_image(&bytes, &image);
+            if let Some((patched, patched_image)) = &patched {
+                let diff = disassembler::diff(&decode()?, &decode_image(patched, patched_image)?);
+                return Ok(diff.to_unified().lines().map(str::to_string).collect());
+            }
+            if let Some(pattern) = &pattern {
+                // matches are separated as by grep
+                let matches: Vec<_> = disassembler::search(&decode()?, pattern, args.context)
+                    .into_iter()
+                    .map(|found| found.lines)
+                    .collect();
+                return Ok(matches.join(&"--".to_string()));
+            }
+            match args.cfg {
+                None if args.functions => {
+                    let instructions = decode()?;
+                    let mut entries = args.entry.clone();
+                    entries.extend(disasm.prologues_dyn(&instructions));
+                    let graph = disassembler::call_graph(&instructions, &entries);
+                    Ok(vec![
+                        serde_json::to_string_pretty(&graph).expect("a graph is serializable")
+                    ])
+                }
+                Some(entry) => {
+                    let cfg = disassembler::cfg(&decode()?, entry);
+                    Ok(match args.graph {
+                        Graph::Dot => cfg.to_dot().lines().map(str::to_string).collect(),
+                        Graph::Json => {
+                            vec![serde_json::to_string_pretty(&cfg)
+                                .expect("a graph is serializable")]
+                        }
+                    })
+                }
+                None => match &image {
+                    Some(image) => image.disassemble(&*disasm, &format),
+                    None => disasm.disassemble_dyn(&bytes, &format),
+                },
+            }
+        })
+        .map_err(|e| match e.allowed().as_slice() {
+            [] => e.to_string(),
+            allowed => format!("{e}, expected one of: {}", allowed.join(", ")),
+        })
+}
+
+/// Parses a decimal, `0x` or `$` prefixed hexadecimal number.
+fn parse_number(s: &str) -> Result<usize, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix('$')) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+
+    parsed.map_err(|e| e.to_string())
+}
+
+fn parse_width(s: &str) -> Result<BitWidth, String> {
+    match s {
+        "8" => Ok(BitWidth::Bit8),
+        "16" => Ok(BitWidth::Bit16),
+        "32" => Ok(BitWidth::Bit32),
+        "64" => Ok(BitWidth::Bit64),
+        _ => Err("expected one of: 8, 16, 32, 64".to_string()),
+    }
+}
+
+fn parse_truncated(s: &str) -> Result<Truncated, String> {
+    match s {
+        "data" => Ok(Truncated::Data),
+        "comment" => Ok(Truncated::Comment),
+        "error" => Ok(Truncated::Error),
+        _ => Err("expected one of: data, comment, error".to_string()),
+    }
+}
+
+fn parse_invalid(s: &str) -> Result<Invalid, String> {
+    match s {
+        "raw" => Ok(Invalid::Raw),
+        "data" => Ok(Invalid::Data),
+        "placeholder" => Ok(Invalid::Placeholder),
+        "error" => Ok(Invalid::Error),
+        _ => Err("expected one of: raw, data, placeholder, error".to_string()),
+    }
+}
+
+fn parse_resync(s: &str) -> Result<Resync, String> {
+    match s {
+        "length" => Ok(Resync::Length),
+        "byte" => Ok(Resync::Byte),
+        _ => Err("expected one of: length, byte".to_string()),
+    }
+}
+
+fn parse_symbol_format(s: &str) -> Result<SymbolFormat, String> {
+    match s {
+        "vice" => Ok(SymbolFormat::Vice),
+        "ld65-dbg" => Ok(SymbolFormat::Ld65Dbg),
+        "ld65-map" => Ok(SymbolFormat::Ld65Map),
+        "fceux" => Ok(SymbolFormat::Fceux),
+        "mesen" => Ok(SymbolFormat::Mesen),
+        "assignments" => Ok(SymbolFormat::Assignments),
+        _ => {
+            Err("expected one of: vice, ld65-dbg, ld65-map, fceux, mesen, assignments".to_string())
+        }
+    }
+}
+
+fn parse_input(s: &str) -> Result<Input, String> {
+    match s {
+        "raw" => Ok(Input::Raw),
+        #[cfg(feature = "elf")]
+        "elf" => Ok(Input::Elf),
+        #[cfg(feature = "pe")]
+        "pe" => Ok(Input::Pe),
+        #[cfg(feature = "commodore")]
+        "prg" => Ok(Input::Prg),
+        #[cfg(feature = "commodore")]
+        "t64" => Ok(Input::T64 { file: None }),
+        #[cfg(feature = "commodore")]
+        "d64" => Ok(Input::D64 { file: None }),
+        #[cfg(feature = "nes")]
+        "nes" => Ok(Input::Nes),
+        #[cfg(feature = "hex")]
+        "hex" => Ok(Input::Hex),
+        #[cfg(feature = "hex")]
+        "srec" => Ok(Input::Srec),
+        _ => Err(format!("expected one of: {}", Input::NAMES.join(", "))),
+    }
+}
+
+fn parse_graph(s: &str) -> Result<Graph, String> {
+    match s {
+        "dot" => Ok(Graph::Dot),
+        "json" => Ok(Graph::Json),
+        _ => Err("expected one of: dot, json".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        Args::command().debug_assert();
+
+        assert_eq!(parse_number("0xC000"), Ok(0xC000));
+        assert_eq!(parse_number("$c000"), Ok(0xC000));
+        assert_eq!(parse_number("49152"), Ok(0xC000));
+        assert!(parse_number("C000").is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use clap::{Parser, ValueEnum};
+
+/// A web service that disassembles binary code.
+///
+/// Every option can also be set with an environment variable.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Config {
+    /// Address to bind to.
+    #[arg(long, env = "DISASM_ADDRESS", default_value_t = IpAddr::V4(Ipv4Addr::LOCALHOST))]
+    pub address: IpAddr,
+    /// Port to listen on.
+    #[arg(long, short, env = "DISASM_PORT", default_value_t = 9999)]
+    pub port: u16,
+    /// Log filter, e.g. `info` or `disassembler_server=debug,tower=warn`.
+    #[arg(long, env = "DISASM_LOG", default_value = "info")]
+    pub log: String,
+    /// Log output format.
+    #[arg(long, env = "DISASM_LOG_FORMAT", value_enum, default_value_t)]
+    pub log_format: LogFormat,
+    /// Maximum request body size in bytes.
+    #[arg(long, env = "DISASM_MAX_BODY_SIZE", default_value_t = 2 * 1024 * 1024)]
+    pub max_body_size: usize,
+    /// Number of runtime worker threads, the number of CPU cores by default.
+    #[arg(long, env = "DISASM_WORKER_THREADS")]
+    pub worker_threads: Option<usize>,
+}
+
+impl Config {
+    /// Socket address to listen on.
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+}
+
+/// [tracing_subscriber] output format.
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Full,
+    Compact,
+    Pretty,
+    Json,
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        Config::command().debug_assert();
+
+        let config = Config::try_parse_from([
+            "disassembler-server",
+            "--address",
+            "0.0.0.0",
+            "-p",
+            "8080",
+            "--log-format",
+            "json",
+        ])
+        .unwrap();
+
+        assert_eq!(config.socket_addr(), "0.0.0.0:8080".parse().unwrap());
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.worker_threads, None);
+    }
+}
@@ -0,0 +1,530 @@
+//! Architecture-independent analysis of decoded instructions.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+/// An instruction decoded for analysis, see [Disassembler::decode](super::Disassembler::decode).
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct DecodedInstruction {
+    /// Address as it is shown in the output.
+    pub address: usize,
+    /// Instruction bytes.
+    pub bytes: Vec<u8>,
+    /// Instruction in the assembly syntax.
+    pub text: String,
+    /// Addresses the instruction refers to.
+    pub references: Vec<Reference>,
+    /// How the instruction transfers control.
+    pub flow: FlowKind,
+}
+
+impl DecodedInstruction {
+    /// Address of the next instruction, `None` at the end of the address space.
+    pub fn next(&self) -> Option<usize> {
+        self.address.checked_add(self.bytes.len())
+    }
+
+    /// Known targets of a branch or a call.
+    fn targets(&self, kind: RefKind) -> impl Iterator<Item = usize> + '_ {
+        self.references
+            .iter()
+            .filter(move |reference| reference.kind == kind)
+            .map(|reference| reference.address)
+    }
+}
+
+/// How an instruction transfers control.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum FlowKind {
+    /// Continues with the next instruction.
+    Next,
+    /// An unconditional jump.
+    Branch,
+    ConditionalBranch,
+    Call,
+    /// A jump to a computed address.
+    IndirectBranch,
+    /// A call of a computed address.
+    IndirectCall,
+    Return,
+    /// Execution does not continue, e.g. an exception.
+    Stop,
+}
+
+impl FlowKind {
+    /// Whether the next instruction may be executed after this one.
+    pub fn falls_through(self) -> bool {
+        matches!(
+            self,
+            Self::Next | Self::ConditionalBranch | Self::Call | Self::IndirectCall
+        )
+    }
+}
+
+/// An address an instruction refers to.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct Reference {
+    pub address: usize,
+    pub kind: RefKind,
+}
+
+/// How an instruction refers to an address.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum RefKind {
+    /// A conditional or unconditional jump.
+    Branch,
+    /// A subroutine call.
+    Call,
+    /// A memory read.
+    Read,
+    /// A memory write, including read-modify-write.
+    Write,
+    /// A pointer read by an indirect jump, call or access.
+    Indirect,
+}
+
+impl RefKind {
+    /// Lower case name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Branch => "branch",
+            Self::Call => "call",
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Indirect => "indirect",
+        }
+    }
+}
+
+/// An instruction referring to an address.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct Xref {
+    /// The referenced address.
+    pub address: usize,
+    /// Address of the referring instruction.
+    pub from: usize,
+    pub kind: RefKind,
+}
+
+/// Cross references of `instructions`, sorted by the referenced address.
+pub fn xrefs(instructions: &[DecodedInstruction]) -> Vec<Xref> {
+    let mut xrefs: Vec<_> = instructions
+        .iter()
+        .flat_map(|instruction| {
+            instruction.references.iter().map(|reference| Xref {
+                address: reference.address,
+                from: instruction.address,
+                kind: reference.kind,
+            })
+        })
+        .collect();
+    xrefs.sort_by_key(|xref| (xref.address, xref.from, xref.kind));
+    xrefs.dedup();
+    xrefs
+}
+
+/// Control flow graph of a function.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Cfg {
+    /// Function entry address.
+    pub entry: usize,
+    /// Blocks sorted by address.
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+/// Instructions executed one after another.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct BasicBlock {
+    /// Address of the first instruction.
+    pub start: usize,
+    /// Address after the last instruction.
+    pub end: usize,
+    pub instructions: Vec<DecodedInstruction>,
+}
+
+/// A control transfer from the last instruction of a block.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct Edge {
+    /// Start of the block.
+    pub from: usize,
+    /// Target address, [None] if it is computed.
+    pub to: Option<usize>,
+    pub kind: EdgeKind,
+}
+
+/// Kind of an [Edge].
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum EdgeKind {
+    FallThrough,
+    /// A taken branch.
+    Taken,
+    Call,
+    Indirect,
+}
+
+impl EdgeKind {
+    /// Lower case name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::FallThrough => "fall-through",
+            Self::Taken => "taken",
+            Self::Call => "call",
+            Self::Indirect => "indirect",
+        }
+    }
+}
+
+/// Instructions by address.
+type Instructions<'a> = BTreeMap<usize, &'a DecodedInstruction>;
+
+fn by_address(instructions: &[DecodedInstruction]) -> Instructions<'_> {
+    instructions
+        .iter()
+        .map(|instruction| (instruction.address, instruction))
+        .collect()
+}
+
+/// Follows control flow from `entry` without following calls and entering `other` functions.
+/// Returns addresses of the reached instructions and starts of the basic blocks.
+fn reach(
+    by_address: &Instructions,
+    entry: usize,
+    other: &BTreeSet<usize>,
+) -> (BTreeSet<usize>, BTreeSet<usize>) {
+    let mut reachable = BTreeSet::new();
+    let mut leaders = BTreeSet::from([entry]);
+    let mut queue = vec![entry];
+    while let Some(address) = queue.pop() {
+        let Some(instruction) = by_address.get(&address) else {
+            continue;
+        };
+        if (address != entry && other.contains(&address)) || !reachable.insert(address) {
+            continue;
+        }
+
+        let taken: Vec<_> = match instruction.flow {
+            FlowKind::Branch | FlowKind::ConditionalBranch => {
+                instruction.targets(RefKind::Branch).collect()
+            }
+            _ => vec![],
+        };
+        if instruction.flow != FlowKind::Next {
+            leaders.extend(&taken);
+            leaders.extend(instruction.next());
+        }
+        if instruction.flow.falls_through() {
+            queue.extend(instruction.next());
+        }
+        queue.extend(taken);
+    }
+
+    (reachable, leaders)
+}
+
+/// Control flow graph of the function at `entry`, callees are not included.
+pub fn cfg(instructions: &[DecodedInstruction], entry: usize) -> Cfg {
+    let by_address = by_address(instructions);
+    let (reachable, leaders) = reach(&by_address, entry, &BTreeSet::new());
+
+    let mut blocks = vec![];
+    let mut edges = vec![];
+    for leader in leaders.iter().filter(|leader| reachable.contains(leader)) {
+        let mut block = vec![];
+        let mut address = *leader;
+        let last = loop {
+            let instruction = by_address[&address];
+            block.push(instruction.clone());
+            match instruction.next() {
+                Some(next)
+                    if instruction.flow == FlowKind::Next
+                        && !leaders.contains(&next)
+                        && reachable.contains(&next) =>
+                {
+                    address = next
+                }
+                _ => break instruction,
+            }
+        };
+
+        let mut edge = |to, kind| {
+            edges.push(Edge {
+                from: *leader,
+                to,
+                kind,
+            })
+        };
+        match last.flow {
+            FlowKind::Branch | FlowKind::ConditionalBranch => {
+                last.targets(RefKind::Branch)
+                    .for_each(|target| edge(Some(target), EdgeKind::Taken));
+            }
+            FlowKind::Call => {
+                last.targets(RefKind::Call)
+                    .for_each(|target| edge(Some(target), EdgeKind::Call));
+            }
+            FlowKind::IndirectBranch | FlowKind::IndirectCall => edge(None, EdgeKind::Indirect),
+            FlowKind::Next | FlowKind::Return | FlowKind::Stop => {}
+        }
+        if let Some(next) = last.next() {
+            if last.flow.falls_through() && reachable.contains(&next) {
+                edge(Some(next), EdgeKind::FallThrough);
+            }
+        }
+
+        blocks.push(BasicBlock {
+            start: *leader,
+            end: last.next().unwrap_or(usize::MAX),
+            instructions: block,
+        });
+    }
+
+    Cfg {
+        entry,
+        blocks,
+        edges,
+    }
+}
+
+impl Cfg {
+    /// The graph in the Graphviz DOT language, computed targets are shown as `?`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box fontname=\"monospace\"];\n");
+
+        for block in &self.blocks {
+            let label: String = block
+                .instructions
+                .iter()
+                .map(|instruction| {
+                    let text = instruction.text.replace('\\', "\\\\").replace('"', "\\\"");
+                    format!("{:04X} {text}\\l", instruction.address)
+                })
+                .collect();
+            dot += &format!("    \"{:04X}\" [label=\"{label}\"];\n", block.start);
+        }
+        for edge in &self.edges {
+            let to = edge.to.map_or("?".to_string(), |to| format!("{to:04X}"));
+            dot += &format!(
+                "    \"{:04X}\" -> \"{to}\" [label=\"{}\"];\n",
+                edge.from,
+                edge.kind.name()
+            );
+        }
+
+        dot + "}\n"
+    }
+}
+
+/// Functions and calls between them.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct CallGraph {
+    /// Functions sorted by address.
+    pub functions: Vec<Function>,
+    pub calls: Vec<Call>,
+}
+
+/// Code reachable from a function entry without following calls.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct Function {
+    /// Entry address.
+    pub start: usize,
+    /// Address after the last instruction, the range may contain other code or data.
+    pub end: usize,
+    /// Size of the instructions in bytes.
+    pub size: usize,
+    /// Whether the function calls nothing.
+    pub leaf: bool,
+}
+
+/// A call graph edge.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub struct Call {
+    /// Entry of the calling function.
+    pub caller: usize,
+    pub callee: usize,
+    /// Address of the call instruction.
+    pub from: usize,
+}
+
+/// Call graph of functions at `entries` and the decoded call targets. The first instruction
+/// is an entry if there are none. A branch to another function, e.g. a tail call, is not followed.
+pub fn call_graph(instructions: &[DecodedInstruction], entries: &[usize]) -> CallGraph {
+    let by_address = by_address(instructions);
+
+    let mut starts: BTreeSet<_> = entries.iter().copied().collect();
+    if starts.is_empty() {
+        starts.extend(instructions.first().map(|instruction| instruction.address));
+    }
+    starts.extend(
+        instructions
+            .iter()
+            .filter(|instruction| instruction.flow == FlowKind::Call)
+            .flat_map(|instruction| instruction.targets(RefKind::Call)),
+    );
+    starts.retain(|start| by_address.contains_key(start));
+
+    let mut functions = vec![];
+    let mut calls = vec![];
+    for start in &starts {
+        let (reachable, _) = reach(&by_address, *start, &starts);
+        let body: Vec<_> = reachable
+            .iter()
+            .map(|address| by_address[address])
+            .collect();
+
+        let mut leaf = true;
+        for instruction in &body {
+            match instruction.flow {
+                FlowKind::Call => {
+                    calls.extend(instruction.targets(RefKind::Call).map(|callee| Call {
+                        caller: *start,
+                        callee,
+                        from: instruction.address,
+                    }))
+                }
+                FlowKind::IndirectCall => {}
+                _ => continue,
+            }
+            leaf = false;
+        }
+
+        functions.push(Function {
+            start: *start,
+            end: body
+                .iter()
+                .map(|instruction| instruction.next().unwrap_or(usize::MAX))
+                .max()
+                .unwrap_or(*start),
+            size: body.iter().map(|instruction| instruction.bytes.len()).sum(),
+            leaf,
+        });
+    }
+
+    CallGraph { functions, calls }
+}
+
+/// Text of a comment on the line at `address`, e.g. `xref: $C012, $C1A0`.
+#[cfg(any(feature = "mos6502", feature = "x86"))]
+pub(crate) fn xref_comment(
+    xrefs: &[Xref],
+    address: usize,
+    format: impl Fn(usize) -> String,
+) -> Option<String> {
+    let start = xrefs.partition_point(|xref| xref.address < address);
+    let from: Vec<_> = xrefs[start..]
+        .iter()
+        .take_while(|xref| xref.address == address)
+        .map(|xref| format(xref.from))
+        .collect();
+
+    (!from.is_empty()).then(|| format!("xref: {}", from.join(", ")))
+}
+
+/// Texts of the cross reference table comments, a line per referenced address.
+#[cfg(any(feature = "mos6502", feature = "x86"))]
+pub(crate) fn xref_table(xrefs: &[Xref], format: impl Fn(usize) -> String) -> Vec<String> {
+    let mut table: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for xref in xrefs {
+        table.entry(xref.address).or_default().push(format!(
+            "{} {}",
+            format(xref.from),
+            xref.kind.name()
+        ));
+    }
+
+    let lines = table
+        .into_iter()
+        .map(|(address, from)| format!("{}: {}", format(address), from.join(", ")));
+    ["cross references:".to_string()]
+        .into_iter()
+        .chain(lines)
+        .collect()
+}
+
+#[cfg(all(test, any(feature = "mos6502", feature = "x86")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xrefs() {
+        let instruction = |address, references: &[(usize, RefKind)]| DecodedInstruction {
+            address,
+            bytes: vec![],
+            text: String::new(),
+            flow: FlowKind::Next,
+            references: references
+                .iter()
+                .map(|(address, kind)| Reference {
+                    address: *address,
+                    kind: *kind,
+                })
+                .collect(),
+        };
+        let instructions = [
+            instruction(0xC000, &[(0xD020, RefKind::Write)]),
+            instruction(0xC003, &[(0xC000, RefKind::Branch)]),
+            instruction(0xC005, &[(0xD020, RefKind::Read)]),
+        ];
+
+        let xrefs = xrefs(&instructions);
+        let hex = |address| format!("${address:04X}");
+        assert_eq!(
+            xref_comment(&xrefs, 0xD020, hex).as_deref(),
+            Some("xref: $C000, $C005")
+        );
+        assert_eq!(xref_comment(&xrefs, 0xC003, hex), None);
+        assert_eq!(
+            xref_table(&xrefs, hex),
+            [
+                "cross references:",
+                "$C000: $C003 branch",
+                "$D020: $C000 write, $C005 read",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cfg() {
+        let instruction = |address, size, flow| DecodedInstruction {
+            address,
+            bytes: vec![0; size],
+            text: String::new(),
+            references: vec![],
+            flow,
+        };
+        // 0: indirect call, 2: indirect jump, 3: unreachable
+        let instructions = [
+            instruction(0, 2, FlowKind::IndirectCall),
+            instruction(2, 1, FlowKind::IndirectBranch),
+            instruction(3, 1, FlowKind::Return),
+        ];
+
+        let cfg = cfg(&instructions, 0);
+        assert_eq!(cfg.blocks.len(), 2);
+        assert_eq!(
+            cfg.edges,
+            [
+                Edge {
+                    from: 0,
+                    to: None,
+                    kind: EdgeKind::Indirect
+                },
+                Edge {
+                    from: 0,
+                    to: Some(2),
+                    kind: EdgeKind::FallThrough
+                },
+                Edge {
+                    from: 2,
+                    to: None,
+                    kind: EdgeKind::Indirect
+                },
+            ]
+        );
+        assert!(cfg
+            .to_dot()
+            .contains(r#""0002" -> "?" [label="indirect"];"#));
+        assert_eq!(super::cfg(&instructions, 5).blocks, []);
+    }
+}
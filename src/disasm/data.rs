@@ -0,0 +1,362 @@
+//! Splitting the input into code and data, see [Region].
+
+use std::ops::Range;
+
+use crate::{Charset, DataKind, Endian, Region};
+
+/// Bytes shown on a single data line.
+const LINE_SIZE: usize = 8;
+/// Characters shown on a single text line.
+const TEXT_LINE_SIZE: usize = 32;
+
+/// A part of the input shown in the same way.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Segment {
+    /// Input offsets.
+    pub range: Range<usize>,
+    pub kind: DataKind,
+}
+
+/// Splits `len` bytes loaded at `origin` into segments, bytes outside of `regions` are code.
+/// Regions are clipped to the input, an overlapping part belongs to the region that starts first.
+pub(crate) fn segments(len: usize, origin: usize, regions: &[Region]) -> Vec<Segment> {
+    let mut ranges: Vec<_> = regions
+        .iter()
+        .map(|region| {
+            let start = region.start().saturating_sub(origin).min(len);
+            let end = (region.start().saturating_add(region.size()))
+                .saturating_sub(origin)
+                .min(len);
+            (start..end, region.kind())
+        })
+        .filter(|(range, _)| !range.is_empty())
+        .collect();
+    ranges.sort_by_key(|(range, _)| range.start);
+
+    let mut segments = vec![];
+    let mut offset = 0;
+    for (range, kind) in ranges {
+        let start = range.start.max(offset);
+        if start >= range.end {
+            continue;
+        }
+        if offset < start {
+            segments.push(Segment {
+                range: offset..start,
+                kind: DataKind::Code,
+            });
+        }
+        segments.push(Segment {
+            range: start..range.end,
+            kind,
+        });
+        offset = range.end;
+    }
+    if offset < len {
+        segments.push(Segment {
+            range: offset..len,
+            kind: DataKind::Code,
+        });
+    }
+
+    segments
+}
+
+/// Control flow of a decoded instruction.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Flow {
+    /// Instruction size in bytes.
+    pub size: usize,
+    /// Known input offsets the instruction may transfer control to.
+    pub targets: Vec<usize>,
+    /// Whether the next instruction may be executed after this one.
+    pub falls_through: bool,
+}
+
+/// Follows control flow from `entries` input offsets with `decode`, which returns [None]
+/// for an undecodable instruction. Code is not followed into data `regions`.
+/// Returns `regions` with the unreachable bytes added as [DataKind::Bytes].
+pub(crate) fn discover(
+    len: usize,
+    origin: usize,
+    regions: &[Region],
+    entries: &[usize],
+    decode: impl Fn(usize) -> Option<Flow>,
+) -> Vec<Region> {
+    // reachable code and data regions, the rest is shown as bytes
+    let mut known = vec![false; len];
+    for segment in segments(len, origin, regions) {
+        if segment.kind != DataKind::Code {
+            known[segment.range].fill(true);
+        }
+    }
+
+    let mut queue = entries.to_vec();
+    // explicit code regions are entry points as well
+    queue.extend(
+        regions
+            .iter()
+            .filter(|region| region.kind() == DataKind::Code)
+            .map(|region| region.start().saturating_sub(origin)),
+    );
+
+    while let Some(offset) = queue.pop() {
+        if offset >= len || known[offset] {
+            continue;
+        }
+        let Some(flow) = decode(offset) else {
+            continue;
+        };
+
+        let end = (offset + flow.size).min(len);
+        known[offset..end].fill(true);
+        if flow.falls_through {
+            queue.push(end);
+        }
+        queue.extend(flow.targets);
+    }
+
+    let mut regions = regions.to_vec();
+    let mut offset = 0;
+    while offset < len {
+        let size = known[offset..].iter().take_while(|known| !**known).count();
+        if size > 0 {
+            regions.push(Region::new(
+                origin.wrapping_add(offset),
+                size,
+                DataKind::Bytes,
+            ));
+        }
+        offset += size
+            + known[offset + size..]
+                .iter()
+                .take_while(|known| **known)
+                .count();
+    }
+
+    regions
+}
+
+/// A data line.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct Item<'a> {
+    /// Input offset of the first byte.
+    pub offset: usize,
+    pub bytes: &'a [u8],
+    pub value: Value,
+}
+
+/// How [Item::bytes] are interpreted.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Value {
+    Bytes,
+    /// 16-bit numbers.
+    Words(Endian),
+    /// Little endian numbers of the pointer size.
+    Pointers,
+    Text(Vec<Piece>),
+    /// The same byte repeated [Item::bytes] length times.
+    Fill,
+}
+
+/// A part of a text line.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Piece {
+    /// Printable characters, without quotes and backslashes.
+    Str(String),
+    /// A byte without a printable character.
+    Byte(u8),
+}
+
+impl Item<'_> {
+    /// [Item::bytes] as numbers of `size` bytes.
+    pub fn numbers(&self, size: usize, endian: Endian) -> Vec<u64> {
+        self.bytes
+            .chunks(size)
+            .map(|chunk| {
+                let fold = |value, byte: &u8| value << 8 | u64::from(*byte);
+                match endian {
+                    Endian::Little => chunk.iter().rev().fold(0, fold),
+                    Endian::Big => chunk.iter().fold(0, fold),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Splits a data `segment` of `bytes` into lines.
+/// A tail that is too short for a word or a pointer is shown as bytes.
+pub(crate) fn items<'a>(bytes: &'a [u8], segment: &Segment, pointer_size: usize) -> Vec<Item<'a>> {
+    let data = &bytes[segment.range.clone()];
+    let item = |start: usize, bytes, value| Item {
+        offset: segment.range.start + start,
+        bytes,
+        value,
+    };
+
+    // numbers of `size` bytes, `LINE_SIZE` bytes per line at most
+    let numbers = |size: usize, value: Value| {
+        let whole = data.len() - data.len() % size;
+        let per_line = (LINE_SIZE / size).max(1) * size;
+
+        let mut items: Vec<_> = (0..whole)
+            .step_by(per_line)
+            .map(|start| {
+                let end = (start + per_line).min(whole);
+                item(start, &data[start..end], value.clone())
+            })
+            .collect();
+        if whole < data.len() {
+            items.push(item(whole, &data[whole..], Value::Bytes));
+        }
+        items
+    };
+
+    match segment.kind {
+        DataKind::Code | DataKind::Bytes => numbers(1, Value::Bytes),
+        DataKind::Words(endian) => numbers(2, Value::Words(endian)),
+        DataKind::Pointers => numbers(pointer_size, Value::Pointers),
+        DataKind::Text(charset) => (0..data.len())
+            .step_by(TEXT_LINE_SIZE)
+            .map(|start| {
+                let bytes = &data[start..(start + TEXT_LINE_SIZE).min(data.len())];
+                item(start, bytes, Value::Text(text(bytes, charset)))
+            })
+            .collect(),
+        DataKind::Fill => {
+            let mut items = vec![];
+            let mut start = 0;
+            while start < data.len() {
+                let run = data[start..]
+                    .iter()
+                    .take_while(|byte| **byte == data[start])
+                    .count();
+                let value = match run {
+                    1 => Value::Bytes,
+                    _ => Value::Fill,
+                };
+                items.push(item(start, &data[start..start + run], value));
+                start += run;
+            }
+            items
+        }
+    }
+}
+
+/// Splits `bytes` into printable strings and other bytes.
+fn text(bytes: &[u8], charset: Charset) -> Vec<Piece> {
+    let mut pieces = vec![];
+    for byte in bytes {
+        match (char_of(*byte, charset), pieces.last_mut()) {
+            (Some(c), Some(Piece::Str(s))) => s.push(c),
+            (Some(c), _) => pieces.push(Piece::Str(c.to_string())),
+            (None, _) => pieces.push(Piece::Byte(*byte)),
+        }
+    }
+    pieces
+}
+
+/// A printable character, quotes and backslashes are excluded so strings need no escaping.
+fn char_of(byte: u8, charset: Charset) -> Option<char> {
+    let c = match charset {
+        Charset::Ascii => byte,
+        Charset::Petscii => match byte {
+            0x41..=0x5A => byte.to_ascii_lowercase(),
+            0xC1..=0xDA => byte - 0x80,
+            0x5B | 0x5D | 0x20..=0x40 => byte,
+            _ => return None,
+        },
+    };
+
+    match c {
+        b'"' | b'\\' => None,
+        0x20..=0x7E => Some(c as char),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segments() {
+        let regions = [
+            Region::new(0xC004, 4, DataKind::Bytes),
+            Region::new(0xC006, 4, DataKind::Fill),
+            Region::new(0xBFFE, 3, DataKind::Pointers),
+        ];
+
+        let kinds: Vec<_> = segments(12, 0xC000, &regions)
+            .into_iter()
+            .map(|segment| (segment.range, segment.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                (0..1, DataKind::Pointers),
+                (1..4, DataKind::Code),
+                (4..8, DataKind::Bytes),
+                (8..10, DataKind::Fill),
+                (10..12, DataKind::Code),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_items() {
+        let bytes = b"\x34\x12\x78\x56\x9A";
+        let segment = Segment {
+            range: 0..5,
+            kind: DataKind::Words(Endian::Little),
+        };
+
+        let items = items(bytes, &segment, 2);
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].numbers(2, Endian::Little), [0x1234, 0x5678]);
+        assert_eq!((items[1].offset, &items[1].value), (4, &Value::Bytes));
+
+        assert_eq!(
+            text(b"HI\"\x0d\xc1", Charset::Petscii),
+            [
+                Piece::Str("hi".to_string()),
+                Piece::Byte(b'"'),
+                Piece::Byte(0x0D),
+                Piece::Str("A".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discover() {
+        // 0: jump to 3, 2: data, 3: fall through to 4, 4: stop
+        let decode = |offset| match offset {
+            0 => Some(Flow {
+                size: 2,
+                targets: vec![3],
+                falls_through: false,
+            }),
+            3 => Some(Flow {
+                size: 1,
+                targets: vec![],
+                falls_through: true,
+            }),
+            4 => Some(Flow {
+                size: 1,
+                targets: vec![],
+                falls_through: false,
+            }),
+            _ => None,
+        };
+
+        let regions = [Region::new(0x105, 1, DataKind::Fill)];
+        assert_eq!(
+            discover(7, 0x100, &regions, &[0], decode),
+            [
+                Region::new(0x105, 1, DataKind::Fill),
+                Region::new(0x102, 1, DataKind::Bytes),
+                Region::new(0x106, 1, DataKind::Bytes),
+            ]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Architecture detection of unlabeled bytes, see [DisassemblerRegistry::detect].
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ArchOptions, AssemblerOutput, BitWidth, Input};
+
+use super::{DisassemblerRegistry, DynDisassembler, RefKind};
+
+/// Weight of the share of bytes decoded as valid instructions.
+const VALID_WEIGHT: f64 = 0.6;
+/// Weight of the share of branch targets on instruction boundaries.
+const TARGETS_WEIGHT: f64 = 0.3;
+/// Weight of architecture-specific hints, prologues or 6502 vectors.
+const HINTS_WEIGHT: f64 = 0.1;
+
+/// Prologues found for the full hint score.
+const PROLOGUES: usize = 4;
+
+/// A possible architecture of the bytes.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Candidate {
+    /// See [Capabilities::name](super::Capabilities::name).
+    pub arch: String,
+    /// [None] if the architecture does not require one.
+    pub width: Option<BitWidth>,
+    /// From 0 to 1, 1 if the architecture is specified by the container.
+    pub confidence: f64,
+    /// Container format recognized by its magic.
+    pub input: Input,
+}
+
+impl DisassemblerRegistry {
+    /// Ranks the registered architectures by how plausible their disassembly of `bytes` is,
+    /// the most plausible first. A container format that specifies the architecture
+    /// is the only candidate, sections of others are scored.
+    pub fn detect(&self, bytes: &[u8]) -> Vec<Candidate> {
+        let input = Input::detect(bytes);
+        let image = input.load(bytes).ok().flatten();
+        let sections: Vec<&[u8]> = match &image {
+            Some(image) => {
+                if let Some(arch) = image.arch.arch() {
+                    return vec![Candidate {
+                        arch: arch.to_string(),
+                        width: image.arch.width(),
+                        confidence: 1.0,
+                        input,
+                    }];
+                }
+                image
+                    .sections
+                    .iter()
+                    .map(|section| &section.bytes[..])
+                    .collect()
+            }
+            None => vec![bytes],
+        };
+        let size: usize = sections.iter().map(|bytes| bytes.len()).sum();
+
+        let mut candidates = vec![];
+        for capabilities in self.capabilities() {
+            let widths = match capabilities.width_required {
+                true => capabilities.widths.iter().copied().map(Some).collect(),
+                false => vec![None],
+            };
+            for width in widths {
+                let options = ArchOptions::default();
+                let options = match width {
+                    Some(width) => options.with_width(width),
+                    None => options,
+                };
+                let Ok(disasm) = self.get(&capabilities.name, &options) else {
+                    continue;
+                };
+
+                // sections are weighted by their size
+                let Some(score) = sections
+                    .iter()
+                    .map(|bytes| {
+                        Some(score(&*disasm, &capabilities.name, bytes)? * bytes.len() as f64)
+                    })
+                    .sum::<Option<f64>>()
+                else {
+                    continue;
+                };
+                candidates.push(Candidate {
+                    arch: capabilities.name.clone(),
+                    width,
+                    confidence: match size {
+                        0 => 0.0,
+                        size => score / size as f64,
+                    },
+                    input: input.clone(),
+                });
+            }
+        }
+
+        candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        candidates
+    }
+}
+
+/// Plausibility of a linear disassembly of `bytes` from 0 to 1,
+/// [None] if the disassembler cannot decode.
+fn score(disasm: &dyn DynDisassembler, arch: &str, bytes: &[u8]) -> Option<f64> {
+    let instructions = disasm.decode_dyn(bytes, &AssemblerOutput::default()).ok()?;
+    if bytes.is_empty() {
+        return Some(0.0);
+    }
+
+    let boundaries: HashSet<_> = instructions
+        .iter()
+        .map(|instruction| instruction.address)
+        .collect();
+    let valid = instructions
+        .iter()
+        .map(|instruction| instruction.bytes.len())
+        .sum::<usize>() as f64
+        / bytes.len() as f64;
+
+    // targets out of the bytes depend on where they are loaded
+    let targets: Vec<_> = instructions
+        .iter()
+        .flat_map(|instruction| &instruction.references)
+        .filter(|reference| matches!(reference.kind, RefKind::Branch | RefKind::Call))
+        .map(|reference| reference.address)
+        .filter(|address| *address < bytes.len())
+        .collect();
+    let targets = match targets.len() {
+        0 => 0.5,
+        count => {
+            targets
+                .iter()
+                .filter(|target| boundaries.contains(target))
+                .count() as f64
+                / count as f64
+        }
+    };
+
+    let hints = match arch {
+        "mos6502" => vectors(bytes, &boundaries),
+        _ => disasm.prologues_dyn(&instructions).len().min(PROLOGUES) as f64 / PROLOGUES as f64,
+    };
+
+    Some(VALID_WEIGHT * valid + TARGETS_WEIGHT * targets + HINTS_WEIGHT * hints)
+}
+
+/// Share of the NMI, RESET and IRQ vectors at the end of `bytes` pointing to instruction
+/// `boundaries`, as if the bytes were a ROM mapped at the end of the address space.
+fn vectors(bytes: &[u8], boundaries: &HashSet<usize>) -> f64 {
+    const ADDRESS_SPACE: usize = 0x10000;
+    if bytes.len() < 6 || bytes.len() > ADDRESS_SPACE {
+        return 0.0;
+    }
+
+    let origin = ADDRESS_SPACE - bytes.len();
+    let plausible = bytes[bytes.len() - 6..]
+        .chunks(2)
+        .map(|vector| usize::from(u16::from_le_bytes([vector[0], vector[1]])))
+        .filter(|vector| {
+            vector
+                .checked_sub(origin)
+                .is_some_and(|offset| boundaries.contains(&offset))
+        })
+        .count();
+
+    plausible as f64 / 3.0
+}
@@ -0,0 +1,376 @@
+//! Instruction level comparison of two disassemblies, see [diff].
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{DecodedInstruction, RefKind};
+
+/// Unchanged instructions shown around a change.
+const CONTEXT: usize = 3;
+
+/// Instructions of two disassemblies aligned in order.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+/// An instruction of either disassembly.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// Address in the old disassembly, [None] if the instruction is added.
+    pub old: Option<usize>,
+    /// Address in the new disassembly, [None] if the instruction is removed.
+    pub new: Option<usize>,
+    /// Text of the new instruction unless it is removed.
+    pub text: String,
+}
+
+/// Kind of a [Change].
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum ChangeKind {
+    /// The same instruction, possibly at a shifted address.
+    Unchanged,
+    Removed,
+    Added,
+}
+
+/// A single edit of the alignment.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Edit {
+    Keep(usize, usize),
+    Remove(usize),
+    Add(usize),
+}
+
+/// Aligns the `old` and `new` instructions with inserted and removed ones. Branch and call
+/// targets are compared by the alignment, instructions at shifted addresses are unchanged
+/// if their targets shift consistently. Targets out of the disassembly must be the same.
+pub fn diff(old: &[DecodedInstruction], new: &[DecodedInstruction]) -> Diff {
+    let edits = align(&keys(old), &keys(new));
+
+    let moved: HashMap<usize, usize> = edits
+        .iter()
+        .filter_map(|edit| match edit {
+            Edit::Keep(i, j) => Some((old[*i].address, new[*j].address)),
+            _ => None,
+        })
+        .collect();
+    let addresses: HashSet<usize> = old.iter().map(|instruction| instruction.address).collect();
+    let consistent = |old: &DecodedInstruction, new: &DecodedInstruction| {
+        targets(old)
+            .zip(targets(new))
+            .all(|(from, to)| match addresses.contains(&from) {
+                true => moved.get(&from) == Some(&to),
+                false => from == to,
+            })
+    };
+
+    let mut changes = vec![];
+    let removed = |instruction: &DecodedInstruction| Change {
+        kind: ChangeKind::Removed,
+        old: Some(instruction.address),
+        new: None,
+        text: instruction.text.clone(),
+    };
+    let added = |instruction: &DecodedInstruction| Change {
+        kind: ChangeKind::Added,
+        old: None,
+        new: Some(instruction.address),
+        text: instruction.text.clone(),
+    };
+    for edit in edits {
+        match edit {
+            Edit::Keep(i, j) if consistent(&old[i], &new[j]) => changes.push(Change {
+                kind: ChangeKind::Unchanged,
+                old: Some(old[i].address),
+                new: Some(new[j].address),
+                text: new[j].text.clone(),
+            }),
+            Edit::Keep(i, j) => changes.extend([removed(&old[i]), added(&new[j])]),
+            Edit::Remove(i) => changes.push(removed(&old[i])),
+            Edit::Add(j) => changes.push(added(&new[j])),
+        }
+    }
+
+    Diff { changes }
+}
+
+impl Diff {
+    /// Whether any instruction is removed or added.
+    pub fn is_changed(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|change| change.kind != ChangeKind::Unchanged)
+    }
+
+    /// A unified diff with the addresses of the first instructions in the hunk headers,
+    /// unchanged instructions are shown at their new address. Empty if nothing changed.
+    pub fn to_unified(&self) -> String {
+        if !self.is_changed() {
+            return String::new();
+        }
+
+        let mut unified = String::from("--- old\n+++ new\n");
+        let changed: Vec<usize> = (0..self.changes.len())
+            .filter(|index| self.changes[*index].kind != ChangeKind::Unchanged)
+            .collect();
+        let mut index = 0;
+        while index < changed.len() {
+            // changes closer than twice the context share a hunk
+            let start = changed[index].saturating_sub(CONTEXT);
+            while index + 1 < changed.len() && changed[index + 1] - changed[index] <= 2 * CONTEXT {
+                index += 1;
+            }
+            let end = (changed[index] + CONTEXT + 1).min(self.changes.len());
+            index += 1;
+
+            let hunk = &self.changes[start..end];
+            let side = |address: fn(&Change) -> Option<usize>| {
+                let count = hunk
+                    .iter()
+                    .filter(|change| address(change).is_some())
+                    .count();
+                let start = hunk.iter().find_map(address).unwrap_or(0);
+                format!("{start:04X},{count}")
+            };
+            unified += &format!(
+                "@@ -{} +{} @@\n",
+                side(|change| change.old),
+                side(|change| change.new)
+            );
+            for change in hunk {
+                let (prefix, address) = match change.kind {
+                    ChangeKind::Unchanged => (' ', change.new),
+                    ChangeKind::Removed => ('-', change.old),
+                    ChangeKind::Added => ('+', change.new),
+                };
+                unified += &format!("{prefix}{:04X} {}\n", address.unwrap_or(0), change.text);
+            }
+        }
+
+        unified
+    }
+}
+
+/// What an instruction is compared by, the text without its branch and call targets.
+#[derive(Debug, PartialEq, Eq)]
+enum Key<'a> {
+    Text(&'a str),
+    /// The mnemonic and the instruction size.
+    Transfer(&'a str, usize),
+}
+
+fn keys(instructions: &[DecodedInstruction]) -> Vec<Key<'_>> {
+    instructions
+        .iter()
+        .map(|instruction| match targets(instruction).next() {
+            Some(_) => Key::Transfer(
+                instruction
+                    .text
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or_default(),
+                instruction.bytes.len(),
+            ),
+            None => Key::Text(&instruction.text),
+        })
+        .collect()
+}
+
+fn targets(instruction: &DecodedInstruction) -> impl Iterator<Item = usize> + '_ {
+    instruction
+        .references
+        .iter()
+        .filter(|reference| matches!(reference.kind, RefKind::Branch | RefKind::Call))
+        .map(|reference| reference.address)
+}
+
+/// The shortest edit script from `old` to `new` by the linear space variant of the Myers
+/// algorithm, memory is linear in the input length.
+fn align<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let mut edits = vec![];
+    split(old, new, (0, 0), &mut edits);
+    edits
+}
+
+/// Appends the edit script of `old` and `new` starting at the indices `(i, j)`,
+/// the halves around the middle snake are aligned recursively.
+fn split<T: PartialEq>(old: &[T], new: &[T], (i, j): (usize, usize), edits: &mut Vec<Edit>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    edits.extend((0..prefix).map(|p| Edit::Keep(i + p, j + p)));
+    let (old, new, i, j) = (&old[prefix..], &new[prefix..], i + prefix, j + prefix);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    if old.is_empty() || new.is_empty() {
+        edits.extend((0..old.len()).map(|x| Edit::Remove(i + x)));
+        edits.extend((0..new.len()).map(|y| Edit::Add(j + y)));
+    } else {
+        // both ends differ, so the edit distance is at least 2 and both halves are shorter
+        let ((x, y), (u, v)) = middle_snake(old, new);
+        split(&old[..x], &new[..y], (i, j), edits);
+        edits.extend((0..u - x).map(|p| Edit::Keep(i + x + p, j + y + p)));
+        split(&old[u..], &new[v..], (i + u, j + v), edits);
+    }
+
+    let (i, j) = (i + old.len(), j + new.len());
+    edits.extend((0..suffix).map(|p| Edit::Keep(i + p, j + p)));
+}
+
+/// Start and end of the middle snake of a shortest edit script, found by searching from
+/// both ends until the paths overlap.
+fn middle_snake<T: PartialEq>(old: &[T], new: &[T]) -> ((usize, usize), (usize, usize)) {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let delta = n - m;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    // the furthest x on each diagonal k = x - y, from the start and from the end
+    let mut forward = vec![0isize; 2 * offset as usize + 1];
+    let mut backward = forward.clone();
+    let at = |k: isize| (k + offset) as usize;
+    let next = |v: &[isize], k: isize, d: isize| match k == -d
+        || (k != d && v[at(k - 1)] < v[at(k + 1)])
+    {
+        true => v[at(k + 1)],
+        false => v[at(k - 1)] + 1,
+    };
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let start = next(&forward, k, d);
+            let mut x = start;
+            while x < n && x - k < m && old[x as usize] == new[(x - k) as usize] {
+                x += 1;
+            }
+            forward[at(k)] = x;
+            // the diagonal seen from the end
+            let back = delta - k;
+            if delta % 2 != 0 && (-(d - 1)..=d - 1).contains(&back) && x + backward[at(back)] >= n {
+                return (
+                    (start as usize, (start - k) as usize),
+                    (x as usize, (x - k) as usize),
+                );
+            }
+        }
+        // x and y count from the end
+        for k in (-d..=d).step_by(2) {
+            let start = next(&backward, k, d);
+            let mut x = start;
+            while x < n && x - k < m && old[(n - 1 - x) as usize] == new[(m - 1 - x + k) as usize] {
+                x += 1;
+            }
+            backward[at(k)] = x;
+            let front = delta - k;
+            if delta % 2 == 0 && (-d..=d).contains(&front) && forward[at(front)] + x >= n {
+                return (
+                    ((n - x) as usize, (m - x + k) as usize),
+                    ((n - start) as usize, (m - start + k) as usize),
+                );
+            }
+        }
+    }
+
+    unreachable!("the paths overlap within (n + m) / 2 rounds")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlowKind, Reference};
+
+    fn instruction(address: usize, text: &str, target: Option<usize>) -> DecodedInstruction {
+        DecodedInstruction {
+            address,
+            bytes: vec![0; 3],
+            text: text.to_string(),
+            references: target
+                .map(|address| Reference {
+                    address,
+                    kind: RefKind::Call,
+                })
+                .into_iter()
+                .collect(),
+            flow: FlowKind::Next,
+        }
+    }
+
+    #[test]
+    fn test_align() {
+        let edits = align(&[1, 2, 3, 4], &[1, 3, 5, 4]);
+        assert_eq!(
+            edits,
+            [
+                Edit::Keep(0, 0),
+                Edit::Remove(1),
+                Edit::Keep(2, 1),
+                Edit::Add(2),
+                Edit::Keep(3, 3),
+            ]
+        );
+        assert_eq!(align::<u8>(&[], &[]), []);
+        assert_eq!(align(&[], &[1]), [Edit::Add(0)]);
+
+        // unrelated inputs, a trace of every search round would take half a gigabyte
+        let n = 4000;
+        let edits = align(&Vec::from_iter(0..n), &Vec::from_iter(n..2 * n));
+        assert_eq!(edits.len(), 2 * n);
+        assert!(edits.iter().all(|edit| !matches!(edit, Edit::Keep(..))));
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = [
+            instruction(0x00, "JSR $0009", Some(0x09)),
+            instruction(0x03, "LDA $0200", None),
+            instruction(0x06, "JSR $FFD2", Some(0xFFD2)),
+            instruction(0x09, "RTS", None),
+        ];
+        // an inserted instruction shifts the subroutine, the call of it is unchanged
+        let new = [
+            instruction(0x00, "JSR $000C", Some(0x0C)),
+            instruction(0x03, "LDA $0200", None),
+            instruction(0x06, "NOP", None),
+            instruction(0x09, "JSR $FFD2", Some(0xFFD2)),
+            instruction(0x0C, "RTS", None),
+        ];
+
+        let diff = diff(&old, &new);
+        let kinds: Vec<_> = diff.changes.iter().map(|change| change.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                ChangeKind::Unchanged,
+                ChangeKind::Unchanged,
+                ChangeKind::Added,
+                ChangeKind::Unchanged,
+                ChangeKind::Unchanged,
+            ]
+        );
+        assert_eq!(
+            diff.to_unified(),
+            "--- old\n+++ new\n\
+             @@ -0000,4 +0000,5 @@\n \
+             0000 JSR $000C\n \
+             0003 LDA $0200\n\
+             +0006 NOP\n \
+             0009 JSR $FFD2\n \
+             000C RTS\n"
+        );
+
+        // a call to a moved target is changed
+        let mut moved = new.clone();
+        moved[0] = instruction(0x00, "JSR $0009", Some(0x09));
+        let diff = super::diff(&old, &moved);
+        assert_eq!(diff.changes[0].kind, ChangeKind::Removed);
+        assert_eq!(diff.changes[1].kind, ChangeKind::Added);
+        assert!(!super::diff(&old, &old).is_changed());
+        assert_eq!(super::diff(&old, &old).to_unified(), "");
+    }
+}
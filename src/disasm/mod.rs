@@ -1,44 +1,255 @@
 //! Different disassemblers for different architectures.
 
-use std::error::Error as StdError;
+use std::{convert::Infallible, error::Error as StdError, fmt};
 
-use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    Json,
-};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{format::AssemblerOutput, BitWidth};
+use crate::{format::AssemblerOutput, ArchOptions, BitWidth, LoadError, SymbolError};
 
+#[cfg(feature = "mos6502")]
 pub mod mos6502;
+#[cfg(feature = "riscv")]
 pub mod risc_v;
+#[cfg(feature = "x86")]
 pub mod x86;
 
+mod analysis;
+#[cfg(any(feature = "mos6502", feature = "x86"))]
+mod data;
+mod detect;
+mod diff;
+mod registry;
+mod search;
+
+pub use analysis::{
+    call_graph, cfg, xrefs, BasicBlock, Call, CallGraph, Cfg, DecodedInstruction, Edge, EdgeKind,
+    FlowKind, Function, RefKind, Reference, Xref,
+};
+pub use detect::Candidate;
+pub use diff::{diff, Change, ChangeKind, Diff};
+pub use registry::DisassemblerRegistry;
+pub use search::{search, Match, Pattern, PatternError};
+
 /// A general disassembler architecture endpoint error.
 #[derive(Error, Debug)]
 pub enum DisasmError<ArchError: StdError> {
-    #[error("Unsupported disassembler option")]
-    UnsupportedOption,
+    /// Contains the offending [AssemblerOutput] field.
+    #[error("Unsupported disassembler option: {0}")]
+    UnsupportedOption(&'static str),
     #[error("The implementation has not been done")]
     Unimplemented,
-    #[error("Missing disassembler option")]
-    MissingInfo,
+    /// Contains the missing [ArchOptions] field.
+    #[error("Missing disassembler option: {0}")]
+    MissingInfo(&'static str),
+    /// Contains the requested and supported bit widths.
     #[error("Invalid architecture bit width: {0}")]
-    WrongBitWidth(BitWidth),
+    WrongBitWidth(BitWidth, &'static [BitWidth]),
+    /// Contains the requested and registered architectures.
+    #[error("Unknown architecture: {0}")]
+    UnknownArch(String, Vec<String>),
+    /// Contains the input offset of the truncated instruction.
+    #[error("Truncated instruction at offset {0}")]
+    Truncated(usize),
+    /// Contains the input offset of the invalid instruction.
+    #[error("Invalid instruction at offset {0}")]
+    InvalidInstruction(usize),
+    #[error(transparent)]
+    Symbols(SymbolError),
+    #[error(transparent)]
+    Load(LoadError),
+    #[error(transparent)]
+    Pattern(PatternError),
     #[error(transparent)]
     Arch(#[from] ArchError),
 }
 
-impl<E: StdError + IntoResponse> IntoResponse for DisasmError<E> {
-    fn into_response(self) -> Response {
-        let code = match self {
-            Self::UnsupportedOption | Self::Unimplemented => StatusCode::NOT_IMPLEMENTED,
-            Self::WrongBitWidth(_) | Self::MissingInfo => StatusCode::BAD_REQUEST,
-            Self::Arch(e) => return e.into_response(),
-        };
+impl<E: StdError + ErrorDetails + Send + Sync + 'static> DisasmError<E> {
+    /// Erases the architecture error type.
+    pub fn into_dyn(self) -> DisasmError<DynError> {
+        match self {
+            Self::UnsupportedOption(field) => DisasmError::UnsupportedOption(field),
+            Self::Unimplemented => DisasmError::Unimplemented,
+            Self::MissingInfo(field) => DisasmError::MissingInfo(field),
+            Self::WrongBitWidth(width, allowed) => DisasmError::WrongBitWidth(width, allowed),
+            Self::UnknownArch(arch, allowed) => DisasmError::UnknownArch(arch, allowed),
+            Self::Truncated(offset) => DisasmError::Truncated(offset),
+            Self::InvalidInstruction(offset) => DisasmError::InvalidInstruction(offset),
+            Self::Symbols(e) => DisasmError::Symbols(e),
+            Self::Load(e) => DisasmError::Load(e),
+            Self::Pattern(e) => DisasmError::Pattern(e),
+            Self::Arch(e) => DisasmError::Arch(DynError(Box::new(e))),
+        }
+    }
+}
+
+impl<E: StdError + ErrorDetails> ErrorDetails for DisasmError<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::UnsupportedOption(_) | Self::Unimplemented => ErrorKind::Unsupported,
+            Self::MissingInfo(_)
+            | Self::WrongBitWidth(..)
+            | Self::Truncated(_)
+            | Self::InvalidInstruction(_) => ErrorKind::InvalidRequest,
+            Self::UnknownArch(..) => ErrorKind::NotFound,
+            Self::Symbols(e) => e.kind(),
+            Self::Load(e) => e.kind(),
+            Self::Pattern(e) => e.kind(),
+            Self::Arch(e) => e.kind(),
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::UnsupportedOption(_) => "unsupported_option",
+            Self::Unimplemented => "unimplemented",
+            Self::MissingInfo(_) => "missing_option",
+            Self::WrongBitWidth(..) => "invalid_bit_width",
+            Self::UnknownArch(..) => "unknown_arch",
+            Self::Truncated(_) => "truncated_instruction",
+            Self::InvalidInstruction(_) => "invalid_instruction",
+            Self::Symbols(e) => e.code(),
+            Self::Load(e) => e.code(),
+            Self::Pattern(e) => e.code(),
+            Self::Arch(e) => e.code(),
+        }
+    }
+
+    fn field(&self) -> Option<&'static str> {
+        match self {
+            Self::UnsupportedOption(field) | Self::MissingInfo(field) => Some(field),
+            Self::WrongBitWidth(..) => Some("arch.width"),
+            Self::UnknownArch(..) => Some("arch.arch"),
+            Self::Unimplemented | Self::Truncated(_) | Self::InvalidInstruction(_) => None,
+            Self::Symbols(e) => e.field(),
+            Self::Load(e) => e.field(),
+            Self::Pattern(e) => e.field(),
+            Self::Arch(e) => e.field(),
+        }
+    }
+
+    fn allowed(&self) -> Vec<String> {
+        match self {
+            Self::WrongBitWidth(_, allowed) => allowed
+                .iter()
+                .map(|width| (*width as u8).to_string())
+                .collect(),
+            Self::UnknownArch(_, allowed) => allowed.clone(),
+            Self::Symbols(e) => e.allowed(),
+            Self::Load(e) => e.allowed(),
+            Self::Pattern(e) => e.allowed(),
+            Self::Arch(e) => e.allowed(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Error category, defines how an error is reported to a client.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorKind {
+    /// The request is malformed or contains invalid values.
+    InvalidRequest,
+    /// The requested entity does not exist.
+    NotFound,
+    /// The request is valid, but is not supported.
+    Unsupported,
+    /// The error is not caused by the request.
+    Internal,
+}
+
+/// Machine-readable error description.
+pub trait ErrorDetails {
+    /// Error category.
+    fn kind(&self) -> ErrorKind;
+
+    /// A stable error code.
+    fn code(&self) -> &'static str;
+
+    /// The offending request field, e.g. `arch.width`.
+    fn field(&self) -> Option<&'static str> {
+        None
+    }
 
-        (code, Json(self.to_string())).into_response()
+    /// Allowed values of the [ErrorDetails::field].
+    fn allowed(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// For disassemblers that never fail.
+impl ErrorDetails for Infallible {
+    fn kind(&self) -> ErrorKind {
+        match *self {}
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {}
+    }
+}
+
+/// A serializable error.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ErrorBody {
+    /// See [ErrorDetails::code].
+    pub code: String,
+    /// Human-readable error message.
+    pub message: String,
+    /// See [ErrorDetails::field].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// See [ErrorDetails::allowed].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed: Vec<String>,
+}
+
+impl ErrorBody {
+    /// Describes an `error`.
+    pub fn new<E: StdError + ErrorDetails>(error: &E) -> Self {
+        Self {
+            code: error.code().to_string(),
+            message: error.to_string(),
+            field: error.field().map(str::to_string),
+            allowed: error.allowed(),
+        }
+    }
+}
+
+/// An architecture error that can be stored in [DynError].
+trait ArchError: StdError + ErrorDetails + Send + Sync {}
+
+impl<E: StdError + ErrorDetails + Send + Sync> ArchError for E {}
+
+/// A type-erased architecture error, see [DynDisassembler].
+#[derive(Debug)]
+pub struct DynError(Box<dyn ArchError>);
+
+impl fmt::Display for DynError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl StdError for DynError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.0.source()
+    }
+}
+
+impl ErrorDetails for DynError {
+    fn kind(&self) -> ErrorKind {
+        self.0.kind()
+    }
+
+    fn code(&self) -> &'static str {
+        self.0.code()
+    }
+
+    fn field(&self) -> Option<&'static str> {
+        self.0.field()
+    }
+
+    fn allowed(&self) -> Vec<String> {
+        self.0.allowed()
     }
 }
 
@@ -48,15 +259,150 @@ impl<E: StdError + IntoResponse> IntoResponse for DisasmError<E> {
 // It would be better to return a wrapper over abstract ASM instructions that would
 // accept [AssemblerOutput] when formatted, however it is too complicated for this assignment.
 
+/// What a disassembler supports, so clients do not have to hard-code it.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Capabilities {
+    /// Architecture name.
+    pub name: String,
+    /// Supported bit widths.
+    pub widths: Vec<BitWidth>,
+    /// Whether the bit width must be provided or is ignored.
+    pub width_required: bool,
+    /// Supported output syntaxes, the first one is the default.
+    pub syntaxes: Vec<SyntaxInfo>,
+    /// Honored [AssemblerOutput] options.
+    pub output: OutputSupport,
+    /// Whether the disassembler is implemented.
+    pub implemented: bool,
+}
+
+/// An output disassembly syntax.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct SyntaxInfo {
+    /// Syntax name.
+    pub name: String,
+    /// Other accepted names of the syntax.
+    pub aliases: Vec<String>,
+}
+
+/// [AssemblerOutput] options a disassembler honors.
+/// Options that are not listed here are supported by every disassembler.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub struct OutputSupport {
+    /// See [AssemblerOutput::cycles].
+    pub cycles: bool,
+    /// See [AssemblerOutput::symbol_table].
+    pub symbols: bool,
+}
+
+impl OutputSupport {
+    /// Checks that `format` does not request an unsupported option.
+    pub fn check<E: StdError>(&self, format: &AssemblerOutput) -> Result<(), DisasmError<E>> {
+        if format.cycles() && !self.cycles {
+            return Err(DisasmError::UnsupportedOption("format.cycles"));
+        }
+        if format.symbol_table().is_some() && !self.symbols {
+            return Err(DisasmError::UnsupportedOption("format.symbol_table"));
+        }
+
+        Ok(())
+    }
+}
+
 /// An abstraction over different disassemblers.
 pub trait Disassembler {
     /// A specific disassembler error.
-    type Error: StdError;
+    type Error: StdError + ErrorDetails;
+
+    /// Describes the disassembler.
+    fn capabilities() -> Capabilities
+    where
+        Self: Sized;
+
+    /// Constructs the disassembler, validating architecture `options`.
+    fn from_options(options: &ArchOptions) -> Result<Self, DisasmError<Self::Error>>
+    where
+        Self: Sized;
 
     /// Performs a disassembly operation on `bytes` with given `options`.
-    fn disassemble<B: AsRef<[u8]>>(
+    fn disassemble(
         &self,
-        bytes: B,
+        bytes: &[u8],
         format: &AssemblerOutput,
     ) -> Result<Vec<String>, DisasmError<Self::Error>>;
+
+    /// Decodes the code of `bytes` for analysis, as [Disassembler::disassemble] would show it.
+    /// Invalid instructions and data are skipped.
+    fn decode(
+        &self,
+        _bytes: &[u8],
+        _format: &AssemblerOutput,
+    ) -> Result<Vec<DecodedInstruction>, DisasmError<Self::Error>> {
+        Err(DisasmError::Unimplemented)
+    }
+
+    /// A comment line of `text` in the output syntax.
+    fn comment(&self, text: &str) -> String {
+        format!("; {text}")
+    }
+
+    /// Addresses of `instructions` that look like function entries, e.g. a stack frame setup.
+    fn prologues(&self, _instructions: &[DecodedInstruction]) -> Vec<usize> {
+        vec![]
+    }
+}
+
+/// A [Disassembler] with an erased error type, so different architectures
+/// can be stored together, see [DisassemblerRegistry].
+pub trait DynDisassembler: Send + Sync {
+    /// See [Disassembler::disassemble].
+    fn disassemble_dyn(
+        &self,
+        bytes: &[u8],
+        format: &AssemblerOutput,
+    ) -> Result<Vec<String>, DisasmError<DynError>>;
+
+    /// See [Disassembler::decode].
+    fn decode_dyn(
+        &self,
+        bytes: &[u8],
+        format: &AssemblerOutput,
+    ) -> Result<Vec<DecodedInstruction>, DisasmError<DynError>>;
+
+    /// See [Disassembler::prologues].
+    fn prologues_dyn(&self, instructions: &[DecodedInstruction]) -> Vec<usize>;
+
+    /// See [Disassembler::comment].
+    fn comment_dyn(&self, text: &str) -> String;
+}
+
+impl<D> DynDisassembler for D
+where
+    D: Disassembler + Send + Sync,
+    D::Error: Send + Sync + 'static,
+{
+    fn disassemble_dyn(
+        &self,
+        bytes: &[u8],
+        format: &AssemblerOutput,
+    ) -> Result<Vec<String>, DisasmError<DynError>> {
+        self.disassemble(bytes, format)
+            .map_err(DisasmError::into_dyn)
+    }
+
+    fn decode_dyn(
+        &self,
+        bytes: &[u8],
+        format: &AssemblerOutput,
+    ) -> Result<Vec<DecodedInstruction>, DisasmError<DynError>> {
+        self.decode(bytes, format).map_err(DisasmError::into_dyn)
+    }
+
+    fn prologues_dyn(&self, instructions: &[DecodedInstruction]) -> Vec<usize> {
+        self.prologues(instructions)
+    }
+
+    fn comment_dyn(&self, text: &str) -> String {
+        self.comment(text)
+    }
 }
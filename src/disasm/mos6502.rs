@@ -1,60 +1,557 @@
-use std::convert::Infallible;
+use std::{collections::HashMap, convert::Infallible, fmt, ops::Range};
 
 use crate::{
     disasm::DisasmError,
-    format::{AssemblerOutput, ShowAddress},
+    format::{
+        AssemblerOutput, DataKind, Discovery, Endian, Invalid, Region, ShowAddress, Truncated,
+    },
+    ArchOptions, BitWidth,
 };
 
-use super::Disassembler;
+use super::{
+    analysis,
+    data::{self, Flow, Item, Piece, Value},
+    Capabilities, DecodedInstruction, Disassembler, FlowKind, OutputSupport, RefKind, Reference,
+};
+
+pub use opcodes::{AddressingMode, Opcode};
+
+mod opcodes;
+
+/// Options honored by [Mos6502].
+const OUTPUT_SUPPORT: OutputSupport = OutputSupport {
+    cycles: false,
+    symbols: true,
+};
 
 /// MOS6502 disassembler.
 pub struct Mos6502;
 
 impl Disassembler for Mos6502 {
-    /// [rs6502] crate does not have an `Error`.
+    /// Any byte sequence can be decoded.
     type Error = Infallible;
 
-    fn disassemble<B: AsRef<[u8]>>(
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            name: "mos6502".to_string(),
+            widths: vec![BitWidth::Bit8],
+            width_required: false,
+            syntaxes: vec![],
+            output: OUTPUT_SUPPORT,
+            implemented: true,
+        }
+    }
+
+    /// There is only one variant, so `options` are ignored.
+    fn from_options(_options: &ArchOptions) -> Result<Self, DisasmError<Infallible>> {
+        Ok(Self)
+    }
+
+    fn disassemble(
         &self,
-        bytes: B,
+        bytes: &[u8],
         options: &AssemblerOutput,
     ) -> Result<Vec<String>, DisasmError<Infallible>> {
-        if options.symbol_table().is_some() || options.cycles() {
-            return Err(DisasmError::UnsupportedOption);
-        }
+        OUTPUT_SUPPORT.check(options)?;
 
-        let disasm = match *options.address() {
-            ShowAddress::None => rs6502::Disassembler::with_code_only(),
-            ShowAddress::Start(offset) => rs6502::Disassembler::with_offset(offset as u16),
+        let origin = origin(options);
+        let names = options.symbol_names();
+        let label = |res: &mut Vec<String>, address: u16| {
+            if let Some(name) = names.get(&usize::from(address)) {
+                res.push(format!("{name}:"));
+            }
+        };
+        let xrefs = match options.xrefs() {
+            true => analysis::xrefs(&self.decode(bytes, options)?),
+            false => vec![],
+        };
+        let hex = |address: usize| match options.upper_case() {
+            true => format!("${address:04X}"),
+            false => format!("${address:04x}"),
         };
+        let xref_comment = |line: &mut String, address: u16| {
+            if let Some(comment) = analysis::xref_comment(&xrefs, address.into(), hex) {
+                *line += &format!(" ; {comment}");
+            }
+        };
+
+        let mut res = vec![];
+        'listing: for segment in data::segments(bytes.len(), origin, &regions(bytes, options)) {
+            if segment.kind != DataKind::Code {
+                for item in data::items(bytes, &segment, 2) {
+                    let address = origin.wrapping_add(item.offset) as u16;
+                    if stop(options, address) {
+                        break 'listing;
+                    }
+
+                    let text = directive(&item, options.upper_case());
+                    let mut line = match options.address() {
+                        ShowAddress::Start(_) if options.upper_case() => {
+                            format!("{address:04X} {:<8} {text}", "")
+                        }
+                        ShowAddress::Start(_) => format!("{address:04x} {:<8} {text}", ""),
+                        ShowAddress::None => text,
+                    };
+                    xref_comment(&mut line, address);
+                    label(&mut res, address);
+
+                    res.push(line);
+                }
+                continue;
+            }
+
+            for instruction in Instructions::new(bytes, origin).with_range(segment.range) {
+                if stop(options, instruction.address) {
+                    break 'listing;
+                }
+
+                let text = match (instruction.opcode, options.invalid()) {
+                    (None, Invalid::Raw) => instruction.hex(),
+                    (None, Invalid::Placeholder) => "???".to_string(),
+                    (None, Invalid::Error) => {
+                        return Err(DisasmError::InvalidInstruction(instruction.offset))
+                    }
+                    _ => instruction.text_with(&names, options.upper_case()),
+                };
+
+                let mut line = match (options.address(), instruction.opcode, options.invalid()) {
+                    // raw bytes are not padded to the text column
+                    (ShowAddress::Start(_), None, Invalid::Raw) => {
+                        format!("{:04X} {text}", instruction.address)
+                    }
+                    (ShowAddress::Start(_), ..) => instruction.listing(&text, options.upper_case()),
+                    (ShowAddress::None, ..) => text,
+                };
+                if instruction.opcode.is_none() && !options.upper_case() {
+                    line.make_ascii_lowercase();
+                }
+                if instruction.is_truncated() {
+                    match options.truncated() {
+                        Truncated::Data => {}
+                        Truncated::Comment => line += " ; truncated",
+                        Truncated::Error => return Err(DisasmError::Truncated(instruction.offset)),
+                    }
+                }
+                xref_comment(&mut line, instruction.address);
+                label(&mut res, instruction.address);
+
+                res.push(line);
+            }
+        }
+
+        if options.xrefs() {
+            let table = analysis::xref_table(&xrefs, hex);
+            res.extend(table.into_iter().map(|line| format!("; {line}")));
+        }
+
+        Ok(res)
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        options: &AssemblerOutput,
+    ) -> Result<Vec<DecodedInstruction>, DisasmError<Infallible>> {
+        OUTPUT_SUPPORT.check(options)?;
 
-        let disasm = disasm.disassemble_with_addresses(bytes.as_ref());
-
-        let processed = disasm
-            .into_iter()
-            .take_while(|(_, addr)| match options.stop_at() {
-                Some(stop) => usize::from(*addr) <= stop,
-                None => true,
-            })
-            .map(|(mut line, _)| {
-                // strip `\n` added by the library
-                line.pop();
-
-                if options.upper_case() {
-                    line
-                } else {
-                    line.to_ascii_lowercase()
+        let origin = origin(options);
+        let names = options.symbol_names();
+        let mut res = vec![];
+        'listing: for segment in data::segments(bytes.len(), origin, &regions(bytes, options)) {
+            if segment.kind != DataKind::Code {
+                continue;
+            }
+
+            for instruction in Instructions::new(bytes, origin).with_range(segment.range) {
+                if stop(options, instruction.address) {
+                    break 'listing;
+                }
+                if instruction.opcode.is_none() || instruction.is_truncated() {
+                    continue;
                 }
 
-                // TODO: handle symbol map
-            });
+                let text = instruction.text_with(&names, options.upper_case());
+
+                res.push(DecodedInstruction {
+                    address: instruction.address.into(),
+                    bytes: instruction.bytes.to_vec(),
+                    text,
+                    references: instruction.reference().into_iter().collect(),
+                    flow: instruction.flow(),
+                });
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Address of the first byte.
+fn origin(options: &AssemblerOutput) -> usize {
+    match *options.address() {
+        ShowAddress::Start(origin) => origin,
+        ShowAddress::None => 0,
+    }
+}
+
+/// Whether the output stops before `address`, see [AssemblerOutput::stop_at].
+fn stop(options: &AssemblerOutput, address: u16) -> bool {
+    options
+        .stop_at()
+        .is_some_and(|stop| usize::from(address) > stop)
+}
+
+/// Data regions, including discovered ones.
+fn regions(bytes: &[u8], options: &AssemblerOutput) -> Vec<Region> {
+    match options.discovery() {
+        Discovery::Linear => options.regions().to_vec(),
+        Discovery::Recursive(entries) => {
+            discover(bytes, origin(options), options.regions(), entries)
+        }
+    }
+}
+
+/// Interrupt vectors: NMI, RESET and IRQ.
+const VECTORS: u16 = 0xFFFA;
+
+/// Finds code reachable from `entries` and the interrupt vectors, see [Discovery::Recursive].
+fn discover(bytes: &[u8], origin: usize, regions: &[Region], entries: &[usize]) -> Vec<Region> {
+    let offset_of = |address: u16| {
+        let offset = usize::from(address.wrapping_sub(origin as u16));
+        (offset < bytes.len()).then_some(offset)
+    };
+
+    let mut regions = regions.to_vec();
+    let mut entries: Vec<_> = entries
+        .iter()
+        .filter_map(|address| offset_of(*address as u16))
+        .collect();
+    if let Some(vectors) = offset_of(VECTORS).filter(|offset| offset + 6 <= bytes.len()) {
+        let targets = bytes[vectors..vectors + 6]
+            .chunks(2)
+            .filter_map(|vector| offset_of(u16::from_le_bytes([vector[0], vector[1]])));
+        entries.extend(targets);
+        regions.push(Region::new(
+            origin.wrapping_add(vectors),
+            6,
+            DataKind::Pointers,
+        ));
+    }
+
+    data::discover(bytes.len(), origin, &regions, &entries, |offset| {
+        let instruction = Instructions::new(bytes, origin)
+            .with_range(offset..bytes.len())
+            .next()?;
+        instruction.opcode.filter(|_| !instruction.is_truncated())?;
+
+        Some(Flow {
+            size: instruction.bytes.len(),
+            targets: instruction
+                .jump_target()
+                .and_then(offset_of)
+                .into_iter()
+                .collect(),
+            falls_through: instruction.flow().falls_through(),
+        })
+    })
+}
+
+/// A data line as a ca65 directive.
+fn directive(item: &Item, upper_case: bool) -> String {
+    let hex = |value: u64, digits: usize| match upper_case {
+        true => format!("${value:0digits$X}"),
+        false => format!("${value:0digits$x}"),
+    };
+
+    let (keyword, values): (_, Vec<_>) = match &item.value {
+        Value::Bytes => (
+            ".BYTE",
+            item.bytes
+                .iter()
+                .map(|byte| hex(u64::from(*byte), 2))
+                .collect(),
+        ),
+        Value::Words(endian) => (
+            match endian {
+                Endian::Little => ".WORD",
+                Endian::Big => ".DBYT",
+            },
+            item.numbers(2, *endian)
+                .into_iter()
+                .map(|word| hex(word, 4))
+                .collect(),
+        ),
+        Value::Pointers => (
+            ".ADDR",
+            item.numbers(2, Endian::Little)
+                .into_iter()
+                .map(|word| hex(word, 4))
+                .collect(),
+        ),
+        Value::Text(pieces) => (
+            ".BYTE",
+            pieces
+                .iter()
+                .map(|piece| match piece {
+                    Piece::Str(s) => format!("\"{s}\""),
+                    Piece::Byte(byte) => hex(u64::from(*byte), 2),
+                })
+                .collect(),
+        ),
+        Value::Fill => (
+            ".RES",
+            vec![
+                item.bytes.len().to_string(),
+                hex(u64::from(item.bytes[0]), 2),
+            ],
+        ),
+    };
+
+    match upper_case {
+        true => format!("{keyword} {}", values.join(",")),
+        false => format!("{} {}", keyword.to_ascii_lowercase(), values.join(",")),
+    }
+}
+
+/// A decoded instruction.
+/// Displayed as a listing line: address, bytes and [Instruction::text].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Instruction<'a> {
+    /// Input offset of the first byte.
+    pub offset: usize,
+    /// Address of the first byte, wraps around the 16-bit address space.
+    pub address: u16,
+    /// Instruction bytes, the opcode goes first.
+    pub bytes: &'a [u8],
+    /// [None] if the opcode is unknown.
+    pub opcode: Option<Opcode>,
+}
+
+impl Instruction<'_> {
+    /// Whether the input ends before the instruction operand.
+    pub fn is_truncated(&self) -> bool {
+        self.opcode
+            .is_some_and(|opcode| self.bytes.len() < opcode.size())
+    }
+
+    /// Instruction in the assembly syntax.
+    /// An unknown or truncated instruction is shown as a data directive.
+    pub fn text(&self) -> String {
+        self.text_with(&HashMap::new(), true)
+    }
+
+    /// [Instruction::text] in the chosen case, an operand address with a name in `names`
+    /// is shown as the name as it is.
+    pub fn text_with(&self, names: &HashMap<usize, String>, upper_case: bool) -> String {
+        let case = |text: String| match upper_case {
+            true => text,
+            false => text.to_ascii_lowercase(),
+        };
+        let Some(opcode) = self.opcode.filter(|_| !self.is_truncated()) else {
+            return case(self.directive());
+        };
+
+        let bytes = self.bytes;
+        let byte = || u16::from(bytes[1]);
+        let word = || u16::from_le_bytes([bytes[1], bytes[2]]);
+        let address = |address: u16, digits: usize| match names.get(&usize::from(address)) {
+            Some(name) => name.clone(),
+            None => case(format!("${address:0digits$X}")),
+        };
+
+        let (prefix, operand, suffix) = match opcode.mode {
+            AddressingMode::Implied | AddressingMode::Accumulator => {
+                return case(opcode.mnemonic.to_string())
+            }
+            AddressingMode::Immediate => ("#", case(format!("${:02X}", bytes[1])), ""),
+            AddressingMode::ZeroPage => ("", address(byte(), 2), ""),
+            AddressingMode::ZeroPageX => ("", address(byte(), 2), ",X"),
+            AddressingMode::ZeroPageY => ("", address(byte(), 2), ",Y"),
+            AddressingMode::Absolute => ("", address(word(), 4), ""),
+            AddressingMode::AbsoluteX => ("", address(word(), 4), ",X"),
+            AddressingMode::AbsoluteY => ("", address(word(), 4), ",Y"),
+            AddressingMode::Indirect => ("(", address(word(), 4), ")"),
+            AddressingMode::IndirectX => ("(", address(byte(), 2), ",X)"),
+            AddressingMode::IndirectY => ("(", address(byte(), 2), "),Y"),
+            AddressingMode::Relative => {
+                ("", address(self.branch_target().unwrap_or_default(), 4), "")
+            }
+        };
+
+        format!(
+            "{} {prefix}{operand}{}",
+            case(opcode.mnemonic.to_string()),
+            case(suffix.to_string())
+        )
+    }
+
+    /// The address the instruction refers to.
+    pub fn reference(&self) -> Option<Reference> {
+        let opcode = self.opcode.filter(|_| !self.is_truncated())?;
+        let bytes = self.bytes;
+
+        let address = match opcode.mode {
+            AddressingMode::Implied | AddressingMode::Accumulator | AddressingMode::Immediate => {
+                return None
+            }
+            AddressingMode::ZeroPage
+            | AddressingMode::ZeroPageX
+            | AddressingMode::ZeroPageY
+            | AddressingMode::IndirectX
+            | AddressingMode::IndirectY => u16::from(bytes[1]),
+            AddressingMode::Absolute
+            | AddressingMode::AbsoluteX
+            | AddressingMode::AbsoluteY
+            | AddressingMode::Indirect => u16::from_le_bytes([bytes[1], bytes[2]]),
+            AddressingMode::Relative => self.branch_target()?,
+        };
+        let kind = match (opcode.mnemonic, opcode.mode) {
+            (
+                _,
+                AddressingMode::Indirect | AddressingMode::IndirectX | AddressingMode::IndirectY,
+            ) => RefKind::Indirect,
+            ("JMP", _) | (_, AddressingMode::Relative) => RefKind::Branch,
+            ("JSR", _) => RefKind::Call,
+            ("STA" | "STX" | "STY" | "ASL" | "LSR" | "ROL" | "ROR" | "INC" | "DEC", _) => {
+                RefKind::Write
+            }
+            _ => RefKind::Read,
+        };
+
+        Some(Reference {
+            address: address.into(),
+            kind,
+        })
+    }
+
+    /// How the instruction transfers control, [FlowKind::Stop] for an undecodable one.
+    pub fn flow(&self) -> FlowKind {
+        let Some(opcode) = self.opcode.filter(|_| !self.is_truncated()) else {
+            return FlowKind::Stop;
+        };
+
+        match (opcode.mnemonic, opcode.mode) {
+            ("JMP", AddressingMode::Indirect) => FlowKind::IndirectBranch,
+            ("JMP", _) => FlowKind::Branch,
+            ("JSR", _) => FlowKind::Call,
+            ("RTS" | "RTI", _) => FlowKind::Return,
+            ("BRK", _) => FlowKind::Stop,
+            (_, AddressingMode::Relative) => FlowKind::ConditionalBranch,
+            _ => FlowKind::Next,
+        }
+    }
+
+    /// Target of a branch, an absolute jump or a subroutine call.
+    pub fn jump_target(&self) -> Option<u16> {
+        let opcode = self.opcode.filter(|_| !self.is_truncated())?;
+        match (opcode.mnemonic, opcode.mode) {
+            ("JMP" | "JSR", AddressingMode::Absolute) => {
+                Some(u16::from_le_bytes([self.bytes[1], self.bytes[2]]))
+            }
+            _ => self.branch_target(),
+        }
+    }
+
+    /// Target of a relative branch.
+    pub fn branch_target(&self) -> Option<u16> {
+        match self.opcode?.mode {
+            AddressingMode::Relative => {
+                let next = self.address.wrapping_add(self.bytes.len() as u16);
+                Some(next.wrapping_add_signed(self.bytes[1] as i8 as i16))
+            }
+            _ => None,
+        }
+    }
+
+    /// Instruction bytes as a `.BYTE` directive.
+    fn directive(&self) -> String {
+        let bytes: Vec<_> = self
+            .bytes
+            .iter()
+            .map(|byte| format!("${byte:02X}"))
+            .collect();
+        format!(".BYTE {}", bytes.join(","))
+    }
+
+    fn hex(&self) -> String {
+        let hex: Vec<_> = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        hex.join(" ")
+    }
+
+    /// A listing line: address, bytes and `text`.
+    fn listing(&self, text: &str, upper_case: bool) -> String {
+        let mut prefix = format!("{:04X} {:<8}", self.address, self.hex());
+        if !upper_case {
+            prefix.make_ascii_lowercase();
+        }
+        format!("{prefix} {text}")
+    }
+}
 
-        Ok(processed.collect())
+impl fmt::Display for Instruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.listing(&self.text(), true))
+    }
+}
+
+/// Linear sweep decoder.
+/// An unknown opcode is a single byte instruction, so [Resync](crate::Resync) makes no difference.
+pub struct Instructions<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    end: usize,
+    origin: usize,
+}
+
+impl<'a> Instructions<'a> {
+    /// Decodes `bytes` loaded at `origin`.
+    pub fn new(bytes: &'a [u8], origin: usize) -> Self {
+        Self {
+            bytes,
+            offset: 0,
+            end: bytes.len(),
+            origin,
+        }
+    }
+
+    /// Decodes only `range` of the bytes, an instruction crossing its end is truncated.
+    pub fn with_range(mut self, range: Range<usize>) -> Self {
+        self.offset = range.start;
+        self.end = range.end.min(self.bytes.len());
+        self
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Instruction<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.bytes[self.offset..self.end];
+        let opcode = Opcode::decode(*rest.first()?);
+
+        // an unknown opcode is skipped byte by byte, a truncated instruction takes the rest
+        let len = opcode.map_or(1, |opcode| opcode.size().min(rest.len()));
+
+        let instruction = Instruction {
+            offset: self.offset,
+            address: self.origin.wrapping_add(self.offset) as u16,
+            bytes: &rest[..len],
+            opcode,
+        };
+
+        self.offset += len;
+        Some(instruction)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::{Scope, SymbolInfo};
+
     use super::*;
 
     #[test]
@@ -68,4 +565,57 @@ mod tests {
 
         assert_eq!(output, ["0000 A9 BD    LDA #$BD", "0002 A0 BD    LDY #$BD"]);
     }
+
+    #[test]
+    fn test_symbols() {
+        let bytes = [0xa5, 0x12, 0x9d, 0x00, 0x04, 0xd0, 0xfb];
+        let table = HashMap::from([
+            (SymbolInfo::new(0x12, Scope::Local), "ptr".to_string()),
+            (SymbolInfo::new(0x400, Scope::Global), "Screen".to_string()),
+            (SymbolInfo::new(0x400, Scope::Local), "screen".to_string()),
+        ]);
+        let output = Mos6502
+            .disassemble(
+                &bytes,
+                &AssemblerOutput::default()
+                    .with_addresses(ShowAddress::Start(0x3FE))
+                    .with_upper_case(false)
+                    .with_symbol_table(table),
+            )
+            .unwrap();
+
+        assert_eq!(
+            output,
+            [
+                "03fe a5 12    lda ptr",
+                "Screen:",
+                "0400 9d 00 04 sta Screen,x",
+                "0403 d0 fb    bne Screen",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = [0xa9, 0x01, 0x20, 0x28];
+        let disassemble = |truncated| {
+            Mos6502.disassemble(
+                &bytes,
+                &AssemblerOutput::default().with_truncated(truncated),
+            )
+        };
+
+        assert_eq!(
+            disassemble(Truncated::Data).unwrap(),
+            ["0000 A9 01    LDA #$01", "0002 20 28    .BYTE $20,$28"]
+        );
+        assert_eq!(
+            disassemble(Truncated::Comment).unwrap()[1],
+            "0002 20 28    .BYTE $20,$28 ; truncated"
+        );
+        assert!(matches!(
+            disassemble(Truncated::Error),
+            Err(DisasmError::Truncated(2))
+        ));
+    }
 }
@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+
+use crate::ArchOptions;
+
+use super::{Capabilities, DisasmError, Disassembler, DynDisassembler, DynError};
+
+type Constructor = Box<
+    dyn Fn(&ArchOptions) -> Result<Box<dyn DynDisassembler>, DisasmError<DynError>> + Send + Sync,
+>;
+
+struct Entry {
+    capabilities: Capabilities,
+    constructor: Constructor,
+}
+
+/// Disassemblers available at runtime, keyed by [Capabilities::name].
+#[derive(Default)]
+pub struct DisassemblerRegistry {
+    entries: BTreeMap<String, Entry>,
+}
+
+impl DisassemblerRegistry {
+    /// A registry with all disassemblers of this crate enabled by features.
+    pub fn builtin() -> Self {
+        let registry = Self::default();
+        #[cfg(feature = "mos6502")]
+        let registry = registry.with::<super::mos6502::Mos6502>();
+        #[cfg(feature = "x86")]
+        let registry = registry.with::<super::x86::X86>();
+        #[cfg(feature = "riscv")]
+        let registry = registry.with::<super::risc_v::RiscV>();
+
+        registry
+    }
+
+    /// Registers a disassembler, replacing an existing one with the same name.
+    pub fn with<D>(mut self) -> Self
+    where
+        D: Disassembler + Send + Sync + 'static,
+        D::Error: Send + Sync + 'static,
+    {
+        let capabilities = D::capabilities();
+        let constructor: Constructor = Box::new(|options| {
+            D::from_options(options)
+                .map(|disasm| Box::new(disasm) as Box<dyn DynDisassembler>)
+                .map_err(DisasmError::into_dyn)
+        });
+
+        self.entries.insert(
+            capabilities.name.clone(),
+            Entry {
+                capabilities,
+                constructor,
+            },
+        );
+        self
+    }
+
+    /// Constructs a disassembler for `arch`, validating its `options`.
+    pub fn get(
+        &self,
+        arch: &str,
+        options: &ArchOptions,
+    ) -> Result<Box<dyn DynDisassembler>, DisasmError<DynError>> {
+        let entry = self.entries.get(arch).ok_or_else(|| {
+            DisasmError::UnknownArch(arch.to_string(), self.entries.keys().cloned().collect())
+        })?;
+
+        (entry.constructor)(options)
+    }
+
+    /// Descriptions of all registered disassemblers.
+    pub fn capabilities(&self) -> impl Iterator<Item = &Capabilities> {
+        self.entries.values().map(|entry| &entry.capabilities)
+    }
+}
@@ -1,8 +1,11 @@
 use std::convert::Infallible;
 
-use crate::BitWidth;
+use crate::{ArchOptions, BitWidth};
 
-use super::{DisasmError, Disassembler};
+use super::{Capabilities, DisasmError, Disassembler, OutputSupport};
+
+/// Supported bit widths.
+const WIDTHS: &[BitWidth] = &[BitWidth::Bit16, BitWidth::Bit32];
 
 /// RISC-V disassembler.
 pub struct RiscV {
@@ -12,9 +15,10 @@ pub struct RiscV {
 impl RiscV {
     /// Constructs a new [RiscV] disassembler, validating its options.
     pub fn new(width: BitWidth) -> Result<Self, DisasmError<Infallible>> {
-        match width {
-            BitWidth::Bit16 | BitWidth::Bit32 => Ok(Self { width }),
-            unsupported => Err(DisasmError::WrongBitWidth(unsupported)),
+        if WIDTHS.contains(&width) {
+            Ok(Self { width })
+        } else {
+            Err(DisasmError::WrongBitWidth(width, WIDTHS))
         }
     }
 }
@@ -22,9 +26,28 @@ impl RiscV {
 impl Disassembler for RiscV {
     type Error = Infallible;
 
-    fn disassemble<B: AsRef<[u8]>>(
+    fn capabilities() -> Capabilities {
+        Capabilities {
+            name: "riscv".to_string(),
+            widths: WIDTHS.to_vec(),
+            width_required: true,
+            syntaxes: vec![],
+            output: OutputSupport::default(),
+            implemented: false,
+        }
+    }
+
+    fn from_options(options: &ArchOptions) -> Result<Self, DisasmError<Self::Error>> {
+        Self::new(
+            options
+                .width()
+                .ok_or(DisasmError::MissingInfo("arch.width"))?,
+        )
+    }
+
+    fn disassemble(
         &self,
-        _bytes: B,
+        _bytes: &[u8],
         _options: &crate::format::AssemblerOutput,
     ) -> Result<Vec<String>, DisasmError<Self::Error>> {
         let _width = self.width;
@@ -0,0 +1,420 @@
+//! Instruction sequence search, see [search].
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{DecodedInstruction, ErrorDetails, ErrorKind, RefKind};
+
+/// A sequence of instruction patterns separated by `;`, e.g. `LDA #*; STA $D4??`.
+///
+/// A pattern is a mnemonic followed by comma separated operands, without operands
+/// it matches any. Mnemonics and operands are case-insensitive globs where `*` matches
+/// any text and `?` a single character, or one of the operand kinds `<imm>`, `<mem>`,
+/// `<target>` and `<reg>`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Pattern {
+    instructions: Vec<InstructionPattern>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct InstructionPattern {
+    mnemonic: String,
+    /// [None] if any operands match.
+    operands: Option<Vec<OperandPattern>>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum OperandPattern {
+    Glob(String),
+    Kind(OperandKind),
+}
+
+/// Names of the operand kinds in patterns.
+const KINDS: [(&str, OperandKind); 4] = [
+    ("<imm>", OperandKind::Immediate),
+    ("<mem>", OperandKind::Memory),
+    ("<target>", OperandKind::Target),
+    ("<reg>", OperandKind::Register),
+];
+
+/// Kind of an operand inferred from its text and the references of the instruction.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum OperandKind {
+    /// An immediate value.
+    Immediate,
+    /// A memory operand, an address or an indirection.
+    Memory,
+    /// The last operand of a branch or a call.
+    Target,
+    /// Any other name.
+    Register,
+}
+
+/// A pattern parsing error.
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum PatternError {
+    #[error("Empty instruction pattern")]
+    Empty,
+    /// Contains the unknown kind.
+    #[error("Unknown operand kind: {0}")]
+    UnknownKind(String),
+}
+
+impl ErrorDetails for PatternError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidRequest
+    }
+
+    fn code(&self) -> &'static str {
+        "invalid_pattern"
+    }
+
+    fn field(&self) -> Option<&'static str> {
+        Some("pattern")
+    }
+
+    fn allowed(&self) -> Vec<String> {
+        match self {
+            Self::Empty => vec![],
+            Self::UnknownKind(_) => KINDS.map(|(name, _)| name.to_string()).to_vec(),
+        }
+    }
+}
+
+/// Instructions matching a [Pattern].
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Match {
+    /// Address of the first matching instruction.
+    pub address: usize,
+    /// Matching instructions prefixed with `>` and the context around them with a space.
+    pub lines: Vec<String>,
+}
+
+impl FromStr for Pattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let instructions = s
+            .split(';')
+            .map(|pattern| {
+                let (mnemonic, operands) = split(pattern).ok_or(PatternError::Empty)?;
+                let operands = operands
+                    .map(|operands| {
+                        operands
+                            .into_iter()
+                            .map(|operand| {
+                                if !operand.starts_with('<') {
+                                    return Ok(OperandPattern::Glob(operand));
+                                }
+                                KINDS
+                                    .iter()
+                                    .find(|(name, _)| operand.eq_ignore_ascii_case(name))
+                                    .map(|(_, kind)| OperandPattern::Kind(*kind))
+                                    .ok_or(PatternError::UnknownKind(operand))
+                            })
+                            .collect::<Result<_, _>>()
+                    })
+                    .transpose()?;
+                Ok(InstructionPattern {
+                    mnemonic: mnemonic.to_string(),
+                    operands,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { instructions })
+    }
+}
+
+/// Finds sequences of adjacent `instructions` matching the `pattern`, overlapping ones
+/// included. Each match is shown with `context` instructions before and after it.
+pub fn search(
+    instructions: &[DecodedInstruction],
+    pattern: &Pattern,
+    context: usize,
+) -> Vec<Match> {
+    let size = pattern.instructions.len();
+    (0..instructions.len())
+        .filter(|start| {
+            let Some(candidates) = instructions.get(*start..*start + size) else {
+                return false;
+            };
+            candidates
+                .windows(2)
+                .all(|pair| pair[0].next() == Some(pair[1].address))
+                && candidates
+                    .iter()
+                    .zip(&pattern.instructions)
+                    .all(|(instruction, pattern)| pattern.matches(instruction))
+        })
+        .map(|start| {
+            let end = start + size;
+            let lines = (start.saturating_sub(context)..(end + context).min(instructions.len()))
+                .map(|index| {
+                    let instruction = &instructions[index];
+                    let prefix = if (start..end).contains(&index) {
+                        '>'
+                    } else {
+                        ' '
+                    };
+                    format!("{prefix}{:04X} {}", instruction.address, instruction.text)
+                })
+                .collect();
+            Match {
+                address: instructions[start].address,
+                lines,
+            }
+        })
+        .collect()
+}
+
+impl InstructionPattern {
+    fn matches(&self, instruction: &DecodedInstruction) -> bool {
+        let Some((mnemonic, operands)) = split(&instruction.text) else {
+            return false;
+        };
+        if !glob(&self.mnemonic, mnemonic) {
+            return false;
+        }
+        let Some(patterns) = &self.operands else {
+            return true;
+        };
+        let operands = operands.unwrap_or_default();
+
+        patterns.len() == operands.len()
+            && patterns
+                .iter()
+                .zip(&operands)
+                .enumerate()
+                .all(|(index, (pattern, operand))| match pattern {
+                    OperandPattern::Glob(pattern) => glob(pattern, operand),
+                    OperandPattern::Kind(kind) => {
+                        kind_of(instruction, operand, index + 1 == operands.len()) == *kind
+                    }
+                })
+    }
+}
+
+/// The mnemonic and the operands of an instruction with whitespace collapsed,
+/// [None] for an empty text.
+fn split(text: &str) -> Option<(&str, Option<Vec<String>>)> {
+    let text = text.trim();
+    let (mnemonic, operands) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    if mnemonic.is_empty() {
+        return None;
+    }
+    if operands.trim().is_empty() {
+        return Some((mnemonic, None));
+    }
+
+    // commas in brackets separate parts of a single operand, e.g. `($20,X)`
+    let mut parts = vec![String::new()];
+    let mut depth = 0usize;
+    for c in operands.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        parts.last_mut().expect("not empty").push(c);
+    }
+
+    Some((
+        mnemonic,
+        Some(
+            parts
+                .iter()
+                .map(|operand| operand.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect(),
+        ),
+    ))
+}
+
+/// Kind of an `operand` of the `instruction`, the `last` one of a branch is its target.
+fn kind_of(instruction: &DecodedInstruction, operand: &str, last: bool) -> OperandKind {
+    if operand.contains(['[', '(']) || operand.starts_with('*') {
+        return OperandKind::Memory;
+    }
+    let is_reference = |kinds: &[RefKind]| {
+        instruction
+            .references
+            .iter()
+            .any(|reference| kinds.contains(&reference.kind))
+    };
+    if last && is_reference(&[RefKind::Branch, RefKind::Call]) {
+        return OperandKind::Target;
+    }
+    if operand.starts_with('#') {
+        return OperandKind::Immediate;
+    }
+
+    match number(operand) {
+        Some(value)
+            if instruction.references.iter().any(|reference| {
+                reference.address == value
+                    && matches!(
+                        reference.kind,
+                        RefKind::Read | RefKind::Write | RefKind::Indirect
+                    )
+            }) =>
+        {
+            OperandKind::Memory
+        }
+        Some(_) => OperandKind::Immediate,
+        None => OperandKind::Register,
+    }
+}
+
+/// Value of a `$` prefixed hexadecimal, a `0x` prefixed or `h` suffixed one,
+/// or a decimal number. Names like `AH` are not numbers.
+fn number(operand: &str) -> Option<usize> {
+    if let Some(hex) = operand.strip_prefix('$') {
+        return number(hex).or_else(|| usize::from_str_radix(hex, 16).ok());
+    }
+    let operand = operand.strip_prefix('-').unwrap_or(operand);
+    if !operand.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    if let Some(hex) = operand
+        .strip_prefix("0x")
+        .or_else(|| operand.strip_suffix(['h', 'H']))
+    {
+        return usize::from_str_radix(hex, 16).ok();
+    }
+
+    operand.parse().ok()
+}
+
+/// Case-insensitive match of `text` against a `pattern` with `*` and `?` wildcards.
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_ascii_uppercase().chars().collect();
+    let text: Vec<char> = text.to_ascii_uppercase().chars().collect();
+
+    // the last `*` and the text position it was tried at
+    let mut star = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FlowKind, Reference};
+
+    fn instruction(address: usize, size: usize, text: &str) -> DecodedInstruction {
+        DecodedInstruction {
+            address,
+            bytes: vec![0; size],
+            text: text.to_string(),
+            references: vec![],
+            flow: FlowKind::Next,
+        }
+    }
+
+    fn with(
+        mut instruction: DecodedInstruction,
+        address: usize,
+        kind: RefKind,
+    ) -> DecodedInstruction {
+        instruction.references.push(Reference { address, kind });
+        instruction
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob("STA $D4??", "sta $d418"));
+        assert!(glob("*", ""));
+        assert!(glob("a*b*c", "axxbyyc"));
+        assert!(!glob("a*b", "axxc"));
+        assert!(!glob("$D4??", "$D4180"));
+    }
+
+    #[test]
+    fn test_search() {
+        let instructions = [
+            instruction(0xC000, 2, "LDA #$0F"),
+            with(instruction(0xC002, 3, "STA $D418"), 0xD418, RefKind::Write),
+            with(instruction(0xC005, 3, "STA $D020"), 0xD020, RefKind::Write),
+            with(instruction(0xC008, 3, "JSR $C010"), 0xC010, RefKind::Call),
+            instruction(0xC00B, 1, "RTS"),
+        ];
+        let addresses = |pattern: &str| -> Vec<usize> {
+            search(&instructions, &pattern.parse().unwrap(), 0)
+                .iter()
+                .map(|found| found.address)
+                .collect()
+        };
+
+        assert_eq!(addresses("lda #*; sta $D4??"), [0xC000]);
+        assert_eq!(addresses("STA <mem>"), [0xC002, 0xC005]);
+        assert_eq!(addresses("STA"), [0xC002, 0xC005]);
+        assert_eq!(addresses("LDA <imm>"), [0xC000]);
+        assert_eq!(addresses("* <target>"), [0xC008]);
+        assert!(addresses("STA <imm>").is_empty());
+        assert!(addresses("RTS; *").is_empty());
+
+        assert_eq!(
+            search(&instructions, &"JSR".parse().unwrap(), 1)[0].lines,
+            [" C005 STA $D020", ">C008 JSR $C010", " C00B RTS"]
+        );
+    }
+
+    #[test]
+    fn test_x86() {
+        let instructions = [
+            instruction(0, 3, "MOV EAX,[2000h]"),
+            with(
+                instruction(3, 6, "CALL DWORD PTR [3000h]"),
+                0x3000,
+                RefKind::Indirect,
+            ),
+            instruction(9, 2, "SYSCALL"),
+        ];
+        let addresses = |pattern: &str| -> Vec<usize> {
+            search(&instructions, &pattern.parse().unwrap(), 0)
+                .iter()
+                .map(|found| found.address)
+                .collect()
+        };
+
+        assert_eq!(addresses("call <mem>"), [3]);
+        assert_eq!(addresses("mov <reg>, <mem>"), [0]);
+        assert_eq!(addresses("syscall"), [9]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!("LDA;".parse::<Pattern>(), Err(PatternError::Empty));
+        assert_eq!(
+            "LDA <foo>".parse::<Pattern>(),
+            Err(PatternError::UnknownKind("<foo>".to_string()))
+        );
+    }
+}
@@ -1,24 +1,71 @@
-use std::str::FromStr;
+use std::{collections::HashMap, ops::Range, str::FromStr};
 
-use axum::response::IntoResponse;
-use iced_x86::{Decoder, DecoderOptions, Formatter, GasFormatter, Instruction, IntelFormatter};
+use iced_x86::{
+    Decoder, DecoderError, DecoderOptions, FlowControl, Formatter, GasFormatter, Instruction,
+    InstructionInfoFactory, IntelFormatter, OpAccess, OpKind, Register, SymbolResolver,
+    SymbolResult,
+};
 use thiserror::Error;
 
-use crate::{format::AssemblerOutput, BitWidth, ShowAddress};
+use crate::{
+    format::AssemblerOutput, ArchOptions, BitWidth, DataKind, Discovery, Endian, Invalid, Region,
+    Resync, ShowAddress, Truncated,
+};
 
-use super::{DisasmError, Disassembler};
+use super::{
+    analysis,
+    data::{self, Flow, Item, Piece, Value},
+    Capabilities, DecodedInstruction, DisasmError, Disassembler, ErrorDetails, ErrorKind, FlowKind,
+    OutputSupport, RefKind, Reference, SyntaxInfo,
+};
+
+/// Supported bit widths.
+const WIDTHS: &[BitWidth] = &[BitWidth::Bit16, BitWidth::Bit32, BitWidth::Bit64];
+
+/// Options honored by [X86].
+const OUTPUT_SUPPORT: OutputSupport = OutputSupport {
+    cycles: false,
+    symbols: true,
+};
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Iced disassembler error")]
-    Iced(#[source] iced_x86::IcedError), // not in use, PoC
+    Iced(#[source] iced_x86::IcedError),
     #[error("Unsupported syntax: {0}")]
     UnsupportedSyntax(String),
 }
 
-impl IntoResponse for Error {
-    fn into_response(self) -> axum::response::Response {
-        self.to_string().into_response()
+impl ErrorDetails for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Iced(_) => ErrorKind::Internal,
+            Self::UnsupportedSyntax(_) => ErrorKind::InvalidRequest,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Iced(_) => "decoder_error",
+            Self::UnsupportedSyntax(_) => "unsupported_syntax",
+        }
+    }
+
+    fn field(&self) -> Option<&'static str> {
+        match self {
+            Self::Iced(_) => None,
+            Self::UnsupportedSyntax(_) => Some("arch.syntax"),
+        }
+    }
+
+    fn allowed(&self) -> Vec<String> {
+        match self {
+            Self::Iced(_) => vec![],
+            Self::UnsupportedSyntax(_) => Syntax::NAMES
+                .iter()
+                .flat_map(|(_, names)| names.iter().map(|name| name.to_string()))
+                .collect(),
+        }
     }
 }
 
@@ -29,7 +76,7 @@ pub struct X86 {
 }
 
 /// Output disassembly syntax.
-#[derive(Debug, Default, PartialEq)]
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
 pub enum Syntax {
     #[default]
     #[doc(alias = "XSS")]
@@ -38,89 +85,623 @@ pub enum Syntax {
     Att,
 }
 
+impl Syntax {
+    /// All syntaxes with their accepted names, the first name is the canonical one.
+    /// The default syntax goes first.
+    const NAMES: [(Self, &'static [&'static str]); 2] =
+        [(Self::Intel, &["intel"]), (Self::Att, &["att", "at&t"])];
+
+    /// `bytes` as a data directive.
+    fn directive(self, bytes: &[u8], upper_case: bool) -> String {
+        let item = Item {
+            offset: 0,
+            bytes,
+            value: Value::Bytes,
+        };
+        self.data(&item, 1, upper_case)
+    }
+
+    /// A data line as a directive, big endian words are shown as bytes.
+    fn data(self, item: &Item, pointer_size: usize, upper_case: bool) -> String {
+        let numbers = |size: usize, endian| -> Vec<_> {
+            item.numbers(size, endian)
+                .into_iter()
+                .map(|value| self.number(value, size * 2, upper_case))
+                .collect()
+        };
+
+        let (keyword, values) = match (&item.value, self) {
+            (Value::Words(Endian::Little), Self::Intel) => ("dw", numbers(2, Endian::Little)),
+            (Value::Words(Endian::Little), Self::Att) => (".word", numbers(2, Endian::Little)),
+            (Value::Pointers, Self::Intel) => (
+                match pointer_size {
+                    2 => "dw",
+                    4 => "dd",
+                    _ => "dq",
+                },
+                numbers(pointer_size, Endian::Little),
+            ),
+            (Value::Pointers, Self::Att) => (
+                match pointer_size {
+                    2 => ".word",
+                    4 => ".long",
+                    _ => ".quad",
+                },
+                numbers(pointer_size, Endian::Little),
+            ),
+            (Value::Text(pieces), Self::Intel) => (
+                "db",
+                pieces
+                    .iter()
+                    .map(|piece| match piece {
+                        Piece::Str(s) => format!("\"{s}\""),
+                        Piece::Byte(byte) => self.number(u64::from(*byte), 2, upper_case),
+                    })
+                    .collect(),
+            ),
+            (Value::Text(pieces), Self::Att) => {
+                // octal escapes have a fixed length, unlike hexadecimal ones
+                let text: String = pieces
+                    .iter()
+                    .map(|piece| match piece {
+                        Piece::Str(s) => s.clone(),
+                        Piece::Byte(byte) => format!("\\{byte:03o}"),
+                    })
+                    .collect();
+                (".ascii", vec![format!("\"{text}\"")])
+            }
+            (Value::Fill, Self::Intel) => {
+                let byte = self.number(u64::from(item.bytes[0]), 2, upper_case);
+                let (times, db) = match upper_case {
+                    true => ("TIMES", "DB"),
+                    false => ("times", "db"),
+                };
+                return format!("{times} {} {db} {byte}", item.bytes.len());
+            }
+            (Value::Fill, Self::Att) => (
+                ".fill",
+                vec![
+                    item.bytes.len().to_string(),
+                    "1".to_string(),
+                    self.number(u64::from(item.bytes[0]), 2, upper_case),
+                ],
+            ),
+            (Value::Bytes | Value::Words(Endian::Big), Self::Intel) => {
+                ("db", numbers(1, Endian::Little))
+            }
+            (Value::Bytes | Value::Words(Endian::Big), Self::Att) => {
+                (".byte", numbers(1, Endian::Little))
+            }
+        };
+
+        let keyword = match upper_case {
+            true => keyword.to_ascii_uppercase(),
+            false => keyword.to_string(),
+        };
+        format!("{keyword} {}", values.join(","))
+    }
+
+    /// A number in the syntax notation.
+    fn number(self, value: u64, digits: usize, upper_case: bool) -> String {
+        let digits = match upper_case {
+            true => format!("{value:0digits$X}"),
+            false => format!("{value:0digits$x}"),
+        };
+
+        match self {
+            // a number must start with a digit
+            Self::Intel if digits.starts_with(|c: char| c.is_ascii_alphabetic()) => {
+                format!("0{digits}h")
+            }
+            Self::Intel => format!("{digits}h"),
+            Self::Att => format!("0x{digits}"),
+        }
+    }
+
+    /// Line comment prefix.
+    fn comment(self) -> &'static str {
+        match self {
+            Self::Intel => ";",
+            Self::Att => "#",
+        }
+    }
+}
+
 impl FromStr for Syntax {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.to_ascii_lowercase().as_str() {
-            "intel" => Ok(Self::Intel),
-            "att" | "at&t" => Ok(Self::Att),
-            _ => Err(Error::UnsupportedSyntax(s.to_string())),
-        }
+        let requested = s.to_ascii_lowercase();
+
+        Self::NAMES
+            .iter()
+            .find(|(_, names)| names.contains(&requested.as_str()))
+            .map(|(syntax, _)| *syntax)
+            .ok_or_else(|| Error::UnsupportedSyntax(s.to_string()))
     }
 }
 
 impl X86 {
     /// Constructs a new [X86] disassembler, validating its options.
     pub fn new(syntax: Syntax, width: BitWidth) -> Result<Self, DisasmError<Error>> {
-        match width {
-            BitWidth::Bit16 | BitWidth::Bit32 | BitWidth::Bit64 => Ok(Self { syntax, width }),
-            unsupported => Err(DisasmError::WrongBitWidth(unsupported)),
+        if WIDTHS.contains(&width) {
+            Ok(Self { syntax, width })
+        } else {
+            Err(DisasmError::WrongBitWidth(width, WIDTHS))
+        }
+    }
+
+    /// A formatter of the syntax, operands are shown as symbols of `names`.
+    fn formatter(
+        &self,
+        options: &AssemblerOutput,
+        names: &HashMap<usize, String>,
+    ) -> Box<dyn Formatter> {
+        let resolver = (!names.is_empty()).then(|| {
+            Box::new(Symbols {
+                names: names.clone(),
+                origin: origin(options),
+                mask: self.address_mask(),
+            }) as Box<dyn SymbolResolver>
+        });
+        let mut formatter: Box<dyn Formatter> = match self.syntax {
+            Syntax::Intel => Box::new(IntelFormatter::with_options(resolver, None)),
+            Syntax::Att => Box::new(GasFormatter::with_options(resolver, None)),
+        };
+
+        formatter
+            .options_mut()
+            .set_uppercase_all(options.upper_case());
+        formatter
+    }
+
+    /// A decoder of the `range` of `bytes`, instruction pointers are input offsets.
+    fn decoder<'a>(&self, bytes: &'a [u8], range: Range<usize>) -> Decoder<'a> {
+        Decoder::with_ip(
+            self.width as u8 as u32,
+            &bytes[range.clone()],
+            range.start as u64,
+            DecoderOptions::NONE,
+        )
+    }
+
+    /// Data regions, including discovered ones.
+    fn regions(&self, bytes: &[u8], options: &AssemblerOutput) -> Vec<Region> {
+        match options.discovery() {
+            Discovery::Linear => options.regions().to_vec(),
+            Discovery::Recursive(entries) => {
+                self.discover(bytes, origin(options), options.regions(), entries)
+            }
+        }
+    }
+
+    /// Addresses `instruction` refers to, memory accesses are known only for absolute
+    /// and instruction pointer relative operands.
+    fn references(
+        &self,
+        instruction: &Instruction,
+        info_factory: &mut InstructionInfoFactory,
+        origin: usize,
+    ) -> Vec<Reference> {
+        let mut res = vec![];
+        let flow_control = instruction.flow_control();
+
+        if let OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64 =
+            instruction.op0_kind()
+        {
+            res.push(Reference {
+                address: self.address(instruction.near_branch_target() as usize, origin),
+                kind: match flow_control {
+                    FlowControl::Call => RefKind::Call,
+                    _ => RefKind::Branch,
+                },
+            });
+        }
+
+        let Some(operand) =
+            (0..instruction.op_count()).find(|i| instruction.op_kind(*i) == OpKind::Memory)
+        else {
+            return res;
+        };
+        let address = if instruction.is_ip_rel_memory_operand() {
+            self.address(instruction.ip_rel_memory_address() as usize, origin)
+        } else if instruction.memory_base() == Register::None
+            && instruction.memory_index() == Register::None
+            && !matches!(instruction.segment_prefix(), Register::FS | Register::GS)
+        {
+            instruction.memory_displacement64() as usize
+        } else {
+            return res;
+        };
+
+        let kind = match (
+            flow_control,
+            info_factory.info(instruction).op_access(operand),
+        ) {
+            (FlowControl::IndirectBranch | FlowControl::IndirectCall, _) => RefKind::Indirect,
+            (_, OpAccess::Read | OpAccess::CondRead) => RefKind::Read,
+            (
+                _,
+                OpAccess::Write
+                | OpAccess::CondWrite
+                | OpAccess::ReadWrite
+                | OpAccess::ReadCondWrite,
+            ) => RefKind::Write,
+            // e.g. `LEA`
+            _ => return res,
+        };
+        res.push(Reference { address, kind });
+
+        res
+    }
+
+    /// Shown address of the input `offset`.
+    fn address(&self, offset: usize, origin: usize) -> usize {
+        // wrap around the address space
+        ((offset as u64).wrapping_add(origin as u64) & self.address_mask()) as usize
+    }
+
+    /// A listing line of `text` at the input `offset`.
+    fn line(&self, offset: usize, text: String, options: &AssemblerOutput) -> String {
+        match *options.address() {
+            ShowAddress::Start(origin) => {
+                let ip = self.address(offset, origin);
+
+                if options.upper_case() {
+                    format!("0x{ip:08X} {text}")
+                } else {
+                    format!("0x{ip:08x} {text}")
+                }
+            }
+            ShowAddress::None => text,
         }
     }
+
+    /// Finds code reachable from `entries`, see [Discovery::Recursive].
+    fn discover(
+        &self,
+        bytes: &[u8],
+        origin: usize,
+        regions: &[Region],
+        entries: &[usize],
+    ) -> Vec<Region> {
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|address| address.wrapping_sub(origin) & self.address_mask() as usize)
+            .collect();
+
+        data::discover(bytes.len(), origin, regions, &entries, |offset| {
+            let instruction = self.decoder(bytes, offset..bytes.len()).decode();
+            if instruction.is_invalid() {
+                return None;
+            }
+
+            let target = match instruction.op0_kind() {
+                OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64 => {
+                    vec![instruction.near_branch_target() as usize]
+                }
+                _ => vec![],
+            };
+            Some(Flow {
+                size: instruction.len(),
+                targets: target,
+                falls_through: flow(&instruction).falls_through(),
+            })
+        })
+    }
+
+    /// Mask of the addressable memory.
+    fn address_mask(&self) -> u64 {
+        u64::MAX >> (64 - self.width as u32)
+    }
 }
 
 impl Disassembler for X86 {
     type Error = Error;
 
-    fn disassemble<B: AsRef<[u8]>>(
+    fn capabilities() -> Capabilities {
+        let syntaxes = Syntax::NAMES.map(|(_, names)| SyntaxInfo {
+            name: names[0].to_string(),
+            aliases: names[1..].iter().map(|alias| alias.to_string()).collect(),
+        });
+
+        Capabilities {
+            name: "x86".to_string(),
+            widths: WIDTHS.to_vec(),
+            width_required: true,
+            syntaxes: syntaxes.to_vec(),
+            output: OUTPUT_SUPPORT,
+            implemented: true,
+        }
+    }
+
+    fn from_options(options: &ArchOptions) -> Result<Self, DisasmError<Error>> {
+        let syntax = match options.syntax() {
+            Some(requested) => Syntax::from_str(requested)?,
+            None => Syntax::default(),
+        };
+
+        Self::new(
+            syntax,
+            options
+                .width()
+                .ok_or(DisasmError::MissingInfo("arch.width"))?,
+        )
+    }
+
+    fn disassemble(
         &self,
-        bytes: B,
+        bytes: &[u8],
         options: &AssemblerOutput,
     ) -> Result<Vec<String>, DisasmError<Error>> {
-        if options.symbol_table().is_some() || options.cycles() {
-            return Err(DisasmError::UnsupportedOption);
-        }
+        OUTPUT_SUPPORT.check(options)?;
 
-        let mut decoder = Decoder::new(
-            self.width as u8 as u32,
-            bytes.as_ref(),
-            DecoderOptions::NONE,
-        );
-
-        let formatter = match self.syntax {
-            Syntax::Intel => &mut IntelFormatter::new() as &mut dyn Formatter,
-            Syntax::Att => &mut GasFormatter::new() as &mut dyn Formatter,
+        let names = options.symbol_names();
+        let mut formatter = self.formatter(options, &names);
+        let origin = origin(options);
+        let label = |res: &mut Vec<String>, offset: usize| {
+            if let Some(name) = names.get(&self.address(offset, origin)) {
+                res.push(format!("{name}:"));
+            }
+        };
+        let xrefs = match options.xrefs() {
+            true => analysis::xrefs(&self.decode(bytes, options)?),
+            false => vec![],
+        };
+        let hex = |address: usize| match options.upper_case() {
+            true => format!("0x{address:08X}"),
+            false => format!("0x{address:08x}"),
+        };
+        let xref_comment = |line: &mut String, offset: usize| {
+            let address = self.address(offset, origin);
+            if let Some(comment) = analysis::xref_comment(&xrefs, address, hex) {
+                *line += &format!(" {} {comment}", self.syntax.comment());
+            }
         };
-
-        formatter
-            .options_mut()
-            .set_uppercase_all(options.upper_case());
 
         let mut res = vec![];
         let mut output = String::new();
         let mut instruction = Instruction::default();
 
-        while decoder.can_decode() {
-            decoder.decode_out(&mut instruction);
+        'listing: for segment in data::segments(bytes.len(), origin, &self.regions(bytes, options))
+        {
+            if segment.kind != DataKind::Code {
+                let pointer_size = self.width as usize / 8;
+                for item in data::items(bytes, &segment, pointer_size) {
+                    if stop(options, item.offset) {
+                        break 'listing;
+                    }
+
+                    let text = self.syntax.data(&item, pointer_size, options.upper_case());
+                    let mut line = self.line(item.offset, text, options);
+                    xref_comment(&mut line, item.offset);
+                    label(&mut res, item.offset);
 
-            if options
-                .stop_at()
-                .is_some_and(|stop| instruction.ip() >= stop as u64)
-            {
-                break;
+                    res.push(line);
+                }
+                continue;
             }
 
-            output.clear();
-            formatter.format(&instruction, &mut output);
+            let range = segment.range;
+            let mut decoder = self.decoder(bytes, range.clone());
 
-            match *options.address() {
-                ShowAddress::Start(offset) => {
-                    let ip = instruction.ip() + offset as u64;
+            while decoder.can_decode() {
+                decoder.decode_out(&mut instruction);
 
-                    let line = if options.upper_case() {
-                        format!("0x{ip:08X} {output}")
-                    } else {
-                        format!("0x{ip:08x} {output}")
-                    };
-                    res.push(line)
+                let offset = instruction.ip() as usize;
+                if stop(options, offset) {
+                    break 'listing;
                 }
-                ShowAddress::None => res.push(output.clone()),
-            };
+
+                output.clear();
+                let truncated = decoder.last_error() == DecoderError::NoMoreBytes;
+                if truncated {
+                    match options.truncated() {
+                        Truncated::Error => return Err(DisasmError::Truncated(offset)),
+                        Truncated::Data | Truncated::Comment => output.push_str(
+                            &self
+                                .syntax
+                                .directive(&bytes[offset..range.end], options.upper_case()),
+                        ),
+                    }
+                } else if instruction.is_invalid() {
+                    let len = resync(&mut decoder, &instruction, range.start, options)?;
+
+                    match options.invalid() {
+                        Invalid::Raw => formatter.format(&instruction, &mut output),
+                        Invalid::Data => output.push_str(
+                            &self
+                                .syntax
+                                .directive(&bytes[offset..offset + len], options.upper_case()),
+                        ),
+                        Invalid::Placeholder => output.push_str("???"),
+                        Invalid::Error => return Err(DisasmError::InvalidInstruction(offset)),
+                    }
+                } else {
+                    formatter.format(&instruction, &mut output);
+                }
+
+                let mut line = self.line(offset, output.clone(), options);
+                if truncated && options.truncated() == Truncated::Comment {
+                    line += &format!(" {} truncated", self.syntax.comment());
+                }
+                xref_comment(&mut line, offset);
+                label(&mut res, offset);
+
+                res.push(line);
+            }
+        }
+
+        if options.xrefs() {
+            let table = analysis::xref_table(&xrefs, hex);
+            res.extend(
+                table
+                    .into_iter()
+                    .map(|line| format!("{} {line}", self.syntax.comment())),
+            );
+        }
+
+        Ok(res)
+    }
+
+    fn decode(
+        &self,
+        bytes: &[u8],
+        options: &AssemblerOutput,
+    ) -> Result<Vec<DecodedInstruction>, DisasmError<Error>> {
+        OUTPUT_SUPPORT.check(options)?;
+
+        let mut formatter = self.formatter(options, &options.symbol_names());
+        let mut info_factory = InstructionInfoFactory::new();
+        let origin = origin(options);
+
+        let mut res = vec![];
+        let mut instruction = Instruction::default();
+
+        'listing: for segment in data::segments(bytes.len(), origin, &self.regions(bytes, options))
+        {
+            if segment.kind != DataKind::Code {
+                continue;
+            }
+
+            let range = segment.range;
+            let mut decoder = self.decoder(bytes, range.clone());
+
+            while decoder.can_decode() {
+                decoder.decode_out(&mut instruction);
+
+                let offset = instruction.ip() as usize;
+                if stop(options, offset) {
+                    break 'listing;
+                }
+                if instruction.is_invalid() {
+                    resync(&mut decoder, &instruction, range.start, options)?;
+                    continue;
+                }
+
+                let mut text = String::new();
+                formatter.format(&instruction, &mut text);
+
+                res.push(DecodedInstruction {
+                    address: self.address(offset, origin),
+                    bytes: bytes[offset..offset + instruction.len()].to_vec(),
+                    text,
+                    references: self.references(&instruction, &mut info_factory, origin),
+                    flow: flow(&instruction),
+                });
+            }
         }
 
         Ok(res)
     }
+
+    fn comment(&self, text: &str) -> String {
+        format!("{} {text}", self.syntax.comment())
+    }
+
+    /// `ENDBR32`, `ENDBR64` and `PUSH BP` followed by `MOV BP,SP` in any width.
+    fn prologues(&self, instructions: &[DecodedInstruction]) -> Vec<usize> {
+        const ENDBR: [&[u8]; 2] = [&[0xF3, 0x0F, 0x1E, 0xFA], &[0xF3, 0x0F, 0x1E, 0xFB]];
+        const MOV_BP_SP: [&[u8]; 4] = [
+            &[0x89, 0xE5],
+            &[0x8B, 0xEC],
+            &[0x48, 0x89, 0xE5],
+            &[0x48, 0x8B, 0xEC],
+        ];
+
+        instructions
+            .iter()
+            .zip(instructions.iter().skip(1).map(Some).chain([None]))
+            .filter(|(instruction, next)| {
+                ENDBR.contains(&instruction.bytes.as_slice())
+                    || (instruction.bytes == [0x55]
+                        && next.is_some_and(|next| {
+                            Some(next.address) == instruction.next()
+                                && MOV_BP_SP.contains(&next.bytes.as_slice())
+                        }))
+            })
+            .map(|(instruction, _)| instruction.address)
+            .collect()
+    }
+}
+
+/// Shows branch targets and memory operands as symbol names.
+struct Symbols {
+    /// Names by shown address.
+    names: HashMap<usize, String>,
+    origin: usize,
+    mask: u64,
+}
+
+impl SymbolResolver for Symbols {
+    fn symbol(
+        &mut self,
+        instruction: &Instruction,
+        _operand: u32,
+        instruction_operand: Option<u32>,
+        address: u64,
+        _address_size: u32,
+    ) -> Option<SymbolResult<'_>> {
+        // branch targets and instruction pointer relative operands are input offsets
+        let offset = address.wrapping_add(self.origin as u64) & self.mask;
+        let shown = match instruction.op_kind(instruction_operand?) {
+            OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64 => offset,
+            OpKind::Memory if instruction.is_ip_rel_memory_operand() => offset,
+            OpKind::Memory => address,
+            _ => return None,
+        };
+
+        let name = self.names.get(&(shown as usize))?;
+        Some(SymbolResult::with_str(address, name))
+    }
+}
+
+/// How `instruction` transfers control, interrupts return to the next instruction.
+fn flow(instruction: &Instruction) -> FlowKind {
+    match instruction.flow_control() {
+        FlowControl::UnconditionalBranch => FlowKind::Branch,
+        FlowControl::ConditionalBranch => FlowKind::ConditionalBranch,
+        FlowControl::Call => FlowKind::Call,
+        FlowControl::IndirectBranch => FlowKind::IndirectBranch,
+        FlowControl::IndirectCall => FlowKind::IndirectCall,
+        FlowControl::Return => FlowKind::Return,
+        FlowControl::Exception => FlowKind::Stop,
+        _ => FlowKind::Next,
+    }
+}
+
+/// Address of the first byte.
+fn origin(options: &AssemblerOutput) -> usize {
+    match *options.address() {
+        ShowAddress::Start(origin) => origin,
+        ShowAddress::None => 0,
+    }
+}
+
+/// Whether the output stops before the input `offset`, see [AssemblerOutput::stop_at].
+fn stop(options: &AssemblerOutput, offset: usize) -> bool {
+    options.stop_at().is_some_and(|stop| offset >= stop)
+}
+
+/// Moves `decoder` past the invalid `instruction`, returns the skipped size.
+/// `start` is the input offset of the decoded bytes.
+fn resync(
+    decoder: &mut Decoder,
+    instruction: &Instruction,
+    start: usize,
+    options: &AssemblerOutput,
+) -> Result<usize, DisasmError<Error>> {
+    match options.resync() {
+        Resync::Length => Ok(instruction.len()),
+        Resync::Byte => {
+            let next = instruction.ip() + 1;
+            decoder
+                .set_position(next as usize - start)
+                .map_err(Error::Iced)?;
+            decoder.set_ip(next);
+            Ok(1)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -176,4 +757,72 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = [0x90, 0xe8, 0x00];
+        let disassemble = |syntax, truncated| {
+            X86::new(syntax, BitWidth::Bit64).unwrap().disassemble(
+                &bytes,
+                &AssemblerOutput::default()
+                    .with_addresses(ShowAddress::None)
+                    .with_truncated(truncated),
+            )
+        };
+
+        assert_eq!(
+            disassemble(Syntax::Intel, Truncated::Data).unwrap(),
+            ["NOP", "DB 0E8h,00h"]
+        );
+        assert_eq!(
+            disassemble(Syntax::Att, Truncated::Comment).unwrap(),
+            ["NOP", ".BYTE 0xE8,0x00 # truncated"]
+        );
+        assert!(matches!(
+            disassemble(Syntax::Intel, Truncated::Error),
+            Err(DisasmError::Truncated(1))
+        ));
+    }
+
+    #[test]
+    fn test_invalid() {
+        // `C4 C4 90 ..` is an invalid VEX instruction
+        let bytes = [0xc4, 0xc4, 0x90, 0x90];
+        let disassemble = |invalid, resync| {
+            X86::new(Syntax::Intel, BitWidth::Bit64)
+                .unwrap()
+                .disassemble(
+                    &bytes,
+                    &AssemblerOutput::default()
+                        .with_addresses(ShowAddress::None)
+                        .with_invalid(invalid)
+                        .with_resync(resync),
+                )
+        };
+
+        assert_eq!(
+            disassemble(Invalid::Data, Resync::Byte).unwrap()[0],
+            "DB 0C4h"
+        );
+        assert_eq!(
+            disassemble(Invalid::Placeholder, Resync::Length).unwrap()[0],
+            "???"
+        );
+        assert!(matches!(
+            disassemble(Invalid::Error, Resync::Length),
+            Err(DisasmError::InvalidInstruction(0))
+        ));
+    }
+
+    #[test]
+    fn test_prologues() {
+        // NOP; PUSH RBP; MOV RBP,RSP; PUSH RBP; NOP; ENDBR64
+        let bytes = [
+            0x90, 0x55, 0x48, 0x89, 0xe5, 0x55, 0x90, 0xf3, 0x0f, 0x1e, 0xfa,
+        ];
+        let x86 = X86::new(Syntax::Intel, BitWidth::Bit64).unwrap();
+        let instructions = x86.decode(&bytes, &AssemblerOutput::default()).unwrap();
+
+        assert_eq!(x86.prologues(&instructions), [1, 7]);
+    }
 }
@@ -19,6 +19,24 @@ pub struct AssemblerOutput {
     // The conversion is needed due to JSON standard. En enum discriminant is converted to a string.
     #[serde_as(as = "Option<HashMap<serde_with::json::JsonString, _>>")]
     symbol_table: Option<HashMap<SymbolInfo, String>>,
+    /// How to show an instruction cut off by the end of the input.
+    #[serde(default)]
+    truncated: Truncated,
+    /// How to show bytes that do not form a valid instruction.
+    #[serde(default)]
+    invalid: Invalid,
+    /// Where to continue decoding after an invalid instruction.
+    #[serde(default)]
+    resync: Resync,
+    /// Typed memory regions, the rest of the input is code.
+    #[serde(default)]
+    regions: Vec<Region>,
+    /// How to tell code from data.
+    #[serde(default)]
+    discovery: Discovery,
+    /// Show which instructions refer to each address.
+    #[serde(default)]
+    xrefs: bool,
 }
 
 impl Default for AssemblerOutput {
@@ -29,6 +47,12 @@ impl Default for AssemblerOutput {
             cycles: false,
             symbol_table: Default::default(),
             stop_at: None,
+            truncated: Default::default(),
+            invalid: Default::default(),
+            resync: Default::default(),
+            regions: Default::default(),
+            discovery: Default::default(),
+            xrefs: false,
         }
     }
 }
@@ -48,6 +72,120 @@ impl Default for ShowAddress {
     }
 }
 
+/// Policy for a trailing instruction cut off by the end of the input.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum Truncated {
+    /// Show the leftover bytes as a data directive.
+    #[default]
+    Data,
+    /// Show the data directive followed by a `truncated` comment.
+    Comment,
+    /// Fail with [DisasmError::Truncated](crate::DisasmError::Truncated).
+    Error,
+}
+
+/// Policy for bytes that do not form a valid instruction.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum Invalid {
+    /// Show what the decoder reports, as before this policy was added:
+    /// the bytes in hex for MOS 6502, `(BAD)` for x86.
+    #[default]
+    Raw,
+    /// Show the bytes as a data directive.
+    Data,
+    /// Show `???` instead of the instruction.
+    Placeholder,
+    /// Fail with [DisasmError::InvalidInstruction](crate::DisasmError::InvalidInstruction).
+    Error,
+}
+
+/// Where to continue decoding after an invalid instruction.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum Resync {
+    /// Skip the whole invalid instruction as reported by the decoder.
+    #[default]
+    Length,
+    /// Skip only the first byte, the next instruction may start inside the invalid one.
+    Byte,
+}
+
+/// How code is told apart from data.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone)]
+pub enum Discovery {
+    /// Everything outside of [Region]s is code.
+    #[default]
+    Linear,
+    /// Only instructions reachable from the entry point addresses are code, the rest is data.
+    /// MOS 6502 also starts from the interrupt vectors at `$FFFA-$FFFF` if the input covers them.
+    Recursive(Vec<usize>),
+}
+
+/// A typed memory region.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub struct Region {
+    /// Address of the first byte, as it is shown with [ShowAddress::Start].
+    start: usize,
+    /// Size in bytes.
+    size: usize,
+    /// Region contents.
+    kind: DataKind,
+}
+
+impl Region {
+    /// Constructs a new region.
+    pub fn new(start: usize, size: usize, kind: DataKind) -> Self {
+        Self { start, size, kind }
+    }
+
+    /// Address of the first byte.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Size in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Region contents.
+    pub fn kind(&self) -> DataKind {
+        self.kind
+    }
+}
+
+/// Contents of a [Region], defines how it is shown.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum DataKind {
+    /// Instructions.
+    Code,
+    /// Raw bytes.
+    Bytes,
+    /// 16-bit words.
+    Words(Endian),
+    /// Little endian addresses of the architecture size.
+    Pointers,
+    /// Strings, bytes without a printable character are shown as numbers.
+    Text(Charset),
+    /// Runs of a repeated byte.
+    Fill,
+}
+
+/// Byte order.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// Character set of a text [Region].
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum Charset {
+    Ascii,
+    /// Commodore character set, shown as ca65 `c64` target strings:
+    /// unshifted letters in the lower case, shifted ones in the upper case.
+    Petscii,
+}
+
 /// A symbol attributes.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, Clone, Copy)]
 pub struct SymbolInfo {
@@ -61,6 +199,16 @@ impl SymbolInfo {
     pub fn new(address: usize, scope: Scope) -> Self {
         Self { address, scope }
     }
+
+    /// Symbol address.
+    pub fn address(&self) -> usize {
+        self.address
+    }
+
+    /// Symbol scope.
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
 }
 
 /// Defines a symbol scope.
@@ -102,6 +250,42 @@ impl AssemblerOutput {
         self
     }
 
+    /// Choose how to show a truncated trailing instruction.
+    pub fn with_truncated(mut self, truncated: Truncated) -> Self {
+        self.truncated = truncated;
+        self
+    }
+
+    /// Choose how to show invalid instructions.
+    pub fn with_invalid(mut self, invalid: Invalid) -> Self {
+        self.invalid = invalid;
+        self
+    }
+
+    /// Choose where to continue decoding after an invalid instruction.
+    pub fn with_resync(mut self, resync: Resync) -> Self {
+        self.resync = resync;
+        self
+    }
+
+    /// Show `regions` as data, the rest of the input is code.
+    pub fn with_regions(mut self, regions: Vec<Region>) -> Self {
+        self.regions = regions;
+        self
+    }
+
+    /// Choose how code is told apart from data.
+    pub fn with_discovery(mut self, discovery: Discovery) -> Self {
+        self.discovery = discovery;
+        self
+    }
+
+    /// Show cross references as comments and a table at the end of a disassembly output.
+    pub fn with_xrefs(mut self, xrefs: bool) -> Self {
+        self.xrefs = xrefs;
+        self
+    }
+
     /// Show address?
     pub fn address(&self) -> &ShowAddress {
         &self.address
@@ -126,4 +310,47 @@ impl AssemblerOutput {
     pub fn symbol_table(&self) -> Option<&HashMap<SymbolInfo, String>> {
         self.symbol_table.as_ref()
     }
+
+    /// Names of [AssemblerOutput::symbol_table] by address, a global name is preferred.
+    #[cfg(any(feature = "mos6502", feature = "x86"))]
+    pub(crate) fn symbol_names(&self) -> HashMap<usize, String> {
+        let mut symbols: Vec<_> = self.symbol_table.iter().flatten().collect();
+        // local names go first to be replaced
+        symbols.sort_by_key(|(symbol, _)| **symbol);
+
+        symbols
+            .into_iter()
+            .map(|(symbol, name)| (symbol.address(), name.clone()))
+            .collect()
+    }
+
+    /// How to show a truncated trailing instruction?
+    pub fn truncated(&self) -> Truncated {
+        self.truncated
+    }
+
+    /// How to show invalid instructions?
+    pub fn invalid(&self) -> Invalid {
+        self.invalid
+    }
+
+    /// Where to continue decoding after an invalid instruction?
+    pub fn resync(&self) -> Resync {
+        self.resync
+    }
+
+    /// Typed memory regions?
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// How to tell code from data?
+    pub fn discovery(&self) -> &Discovery {
+        &self.discovery
+    }
+
+    /// Show cross references?
+    pub fn xrefs(&self) -> bool {
+        self.xrefs
+    }
 }
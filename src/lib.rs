@@ -4,11 +4,15 @@ use serde::{Deserialize, Serialize};
 
 mod disasm;
 mod format;
+mod loader;
+#[cfg(feature = "server")]
+pub mod server;
+mod symbols;
 
-pub use {disasm::*, format::*};
+pub use {disasm::*, format::*, loader::*, symbols::*};
 
 /// Architecture bit width.
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum BitWidth {
     Bit8 = 8,
@@ -28,3 +32,61 @@ impl fmt::Display for BitWidth {
         .fmt(f)
     }
 }
+
+/// Architecture parameters.
+/// Option can be omitted (and will not be validated) for architectures that support only 1 variant
+/// or do not support it at all.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ArchOptions {
+    /// Target architecture name, see [Capabilities::name].
+    /// Only used by endpoints that are not architecture-specific.
+    arch: Option<String>,
+    /// Target architecture bit width.
+    width: Option<BitWidth>,
+    /// Output disassembly syntax.
+    syntax: Option<String>,
+}
+
+impl ArchOptions {
+    /// Choose a target architecture by its name.
+    pub fn with_arch(mut self, arch: impl Into<String>) -> Self {
+        self.arch = Some(arch.into());
+        self
+    }
+
+    /// Choose a target architecture bit width.
+    pub fn with_width(mut self, width: BitWidth) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Choose an output disassembly syntax.
+    pub fn with_syntax(mut self, syntax: impl Into<String>) -> Self {
+        self.syntax = Some(syntax.into());
+        self
+    }
+
+    /// Target architecture name.
+    pub fn arch(&self) -> Option<&str> {
+        self.arch.as_deref()
+    }
+
+    /// Target architecture bit width.
+    pub fn width(&self) -> Option<BitWidth> {
+        self.width
+    }
+
+    /// Output disassembly syntax.
+    pub fn syntax(&self) -> Option<&str> {
+        self.syntax.as_deref()
+    }
+
+    /// Fills the options missing in `self` from `defaults`.
+    pub fn or(self, defaults: &ArchOptions) -> Self {
+        Self {
+            arch: self.arch.or_else(|| defaults.arch.clone()),
+            width: self.width.or(defaults.width),
+            syntax: self.syntax.or_else(|| defaults.syntax.clone()),
+        }
+    }
+}
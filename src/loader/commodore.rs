@@ -0,0 +1,405 @@
+//! Commodore 8-bit program files and the tape and disk images containing them.
+
+use crate::ArchOptions;
+
+use super::{Image, LoadError, Section};
+
+/// BASIC V2 keywords of tokens starting at 0x80.
+const TOKENS: [&str; 76] = [
+    "END", "FOR", "NEXT", "DATA", "INPUT#", "INPUT", "DIM", "READ", "LET", "GOTO", "RUN", "IF",
+    "RESTORE", "GOSUB", "RETURN", "REM", "STOP", "ON", "WAIT", "LOAD", "SAVE", "VERIFY", "DEF",
+    "POKE", "PRINT#", "PRINT", "CONT", "LIST", "CLR", "CMD", "SYS", "OPEN", "CLOSE", "GET", "NEW",
+    "TAB(", "TO", "FN", "SPC(", "THEN", "NOT", "STEP", "+", "-", "*", "/", "^", "AND", "OR", ">",
+    "=", "<", "SGN", "INT", "ABS", "USR", "FRE", "POS", "SQR", "RND", "LOG", "EXP", "COS", "SIN",
+    "TAN", "ATN", "PEEK", "LEN", "STR$", "VAL", "ASC", "CHR$", "LEFT$", "RIGHT$", "MID$", "GO",
+];
+
+const SYS: u8 = 0x9E;
+
+/// Size of a D64 image of 35 tracks without error bytes.
+const D64_SIZE: usize = 174_848;
+
+/// Sizes of D64 images of 35 and 40 tracks, without and with error bytes.
+pub(super) const D64_SIZES: [usize; 4] = [D64_SIZE, 175_531, 196_608, 197_376];
+
+/// Directory track of a D64 image.
+const D64_DIRECTORY: u8 = 18;
+
+/// Loads a `.prg` file, the first two bytes are the little endian load address.
+pub(super) fn load_prg(bytes: &[u8]) -> Result<Image, LoadError> {
+    program("PRG", "PRG", bytes)
+}
+
+/// Loads the program `file` of a `.t64` tape image, the first one if [None].
+pub(super) fn load_t64(bytes: &[u8], file: Option<&str>) -> Result<Image, LoadError> {
+    const FORMAT: &str = "T64";
+    let malformed = |reason: &str| LoadError::Malformed(FORMAT, reason.to_string());
+
+    if !bytes.starts_with(b"C64") {
+        return Err(malformed("no tape image signature"));
+    }
+    let entries = word(bytes, 0x22).ok_or_else(|| malformed("no directory"))?;
+
+    let mut files = vec![];
+    for index in 0..entries {
+        let entry = bytes
+            .get(0x40 + index * 32..0x60 + index * 32)
+            .ok_or_else(|| malformed("the directory is out of the file"))?;
+        // free entries and memory snapshots
+        if entry[0] != 1 {
+            continue;
+        }
+        let start = u16::from_le_bytes([entry[2], entry[3]]);
+        let end = u16::from_le_bytes([entry[4], entry[5]]);
+        let offset = u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]) as usize;
+        // some tools write a wrong end address, the file is cut at the end of the image
+        let size = usize::from(end.wrapping_sub(start));
+        let contents = bytes
+            .get(offset..bytes.len().min(offset + size))
+            .ok_or_else(|| malformed("a file is out of the image"))?;
+        files.push((text(&entry[0x10..0x20]), (start, contents)));
+    }
+
+    let (name, (start, contents)) = select(files, file, |_| true, FORMAT)?;
+    let mut program = start.to_le_bytes().to_vec();
+    program.extend_from_slice(contents);
+    self::program(FORMAT, &name, &program)
+}
+
+/// Loads the program `file` of a `.d64` disk image, the first one if [None].
+pub(super) fn load_d64(bytes: &[u8], file: Option<&str>) -> Result<Image, LoadError> {
+    const FORMAT: &str = "D64";
+
+    if bytes.len() < D64_SIZE {
+        return Err(LoadError::Malformed(
+            FORMAT,
+            format!("expected at least {D64_SIZE} bytes"),
+        ));
+    }
+    let disk = Disk(bytes);
+
+    let bam = disk.sector(D64_DIRECTORY, 0)?;
+    let mut files = vec![];
+    for sector in disk.chain(bam[0], bam[1])? {
+        for entry in sector.chunks(32) {
+            // closed files have the bit 7 of the type set
+            if entry[2] & 0x80 != 0 {
+                files.push((text(&entry[5..0x15]), (entry[2] & 0x07, entry[3], entry[4])));
+            }
+        }
+    }
+
+    // only program files start with a load address
+    let (name, (_, track, sector)) = select(files, file, |(_, (kind, ..))| *kind == 2, FORMAT)?;
+    let program: Vec<u8> = disk
+        .chain(track, sector)?
+        .into_iter()
+        .flat_map(|sector| {
+            // the link to the next sector or, in the last one, the index of the last byte
+            let last = match sector[0] {
+                0 => usize::from(sector[1]).max(1),
+                _ => 255,
+            };
+            sector[2..=last].to_vec()
+        })
+        .collect();
+    self::program(FORMAT, &name, &program)
+}
+
+/// A program without its load address as a section. A BASIC stub starting it
+/// with `SYS` is shown as notes and the section starts after it.
+fn program(format: &'static str, name: &str, bytes: &[u8]) -> Result<Image, LoadError> {
+    let [low, high, code @ ..] = bytes else {
+        return Err(LoadError::Malformed(format, "no load address".to_string()));
+    };
+    let address = usize::from(u16::from_le_bytes([*low, *high]));
+
+    let mut image = Image {
+        arch: ArchOptions::default().with_arch("mos6502"),
+        ..Default::default()
+    };
+    let mut start = 0;
+    if let Some(stub) = basic(address, code) {
+        image.notes.push(format!("BASIC at {address:#X}"));
+        image.notes.extend(stub.lines);
+        image.entries = vec![stub.entry];
+        start = stub.end;
+    }
+    image.sections.push(Section {
+        name: name.to_string(),
+        address: address + start,
+        bytes: code[start..].to_vec(),
+    });
+
+    Ok(image)
+}
+
+/// A BASIC program starting machine code.
+struct Stub {
+    /// Listing of the program.
+    lines: Vec<String>,
+    /// Offset of the byte after the program.
+    end: usize,
+    /// Address of the first `SYS` statement.
+    entry: usize,
+}
+
+/// Decodes a BASIC program at the start of `code` loaded at `address`, [None] if
+/// it is not one or does not call machine code after it.
+fn basic(address: usize, code: &[u8]) -> Option<Stub> {
+    let mut lines = vec![];
+    let mut entry = None;
+    let mut offset = 0;
+    loop {
+        let link = word(code, offset)?;
+        if link == 0 {
+            offset += 2;
+            break;
+        }
+        let number = word(code, offset + 2)?;
+        let tokens = code.get(offset + 4..)?;
+        let tokens = &tokens[..tokens.iter().position(|byte| *byte == 0)?];
+        offset += 4 + tokens.len() + 1;
+        // the link points to the next line
+        if link != address + offset {
+            return None;
+        }
+
+        lines.push(format!("{number} {}", detokenize(tokens)));
+        entry = entry.or_else(|| sys(tokens));
+    }
+
+    let entry = entry.filter(|entry| (address + offset..address + code.len()).contains(entry))?;
+    Some(Stub {
+        lines,
+        end: offset,
+        entry,
+    })
+}
+
+/// A BASIC line as it is listed.
+fn detokenize(tokens: &[u8]) -> String {
+    let mut line = String::new();
+    let mut quoted = false;
+    for &byte in tokens {
+        match byte {
+            b'"' => {
+                quoted = !quoted;
+                line.push('"');
+            }
+            0x80..=0xCB if !quoted => line.push_str(TOKENS[usize::from(byte - 0x80)]),
+            byte => line.push_str(&character(byte)),
+        }
+    }
+
+    line
+}
+
+/// The address of the first `SYS` statement with a number.
+fn sys(tokens: &[u8]) -> Option<usize> {
+    let mut quoted = false;
+    let index = tokens.iter().position(|byte| {
+        quoted ^= *byte == b'"';
+        !quoted && *byte == SYS
+    })?;
+    let digits: String = tokens[index + 1..]
+        .iter()
+        .skip_while(|byte| matches!(byte, b' ' | b'('))
+        .take_while(|byte| byte.is_ascii_digit())
+        .map(|byte| char::from(*byte))
+        .collect();
+
+    digits.parse().ok()
+}
+
+/// PETSCII text, padding is removed and characters without an ASCII counterpart
+/// are shown as `{$XX}`.
+fn text(bytes: &[u8]) -> String {
+    let end = bytes
+        .iter()
+        .rposition(|byte| !matches!(byte, 0x00 | 0x20 | 0xA0))
+        .map_or(0, |index| index + 1);
+
+    bytes[..end].iter().map(|byte| character(*byte)).collect()
+}
+
+/// A PETSCII character, see [text].
+fn character(byte: u8) -> String {
+    match byte {
+        // letters are upper case in the default character set
+        0x20..=0x5A => char::from(byte).to_string(),
+        byte => format!("{{${byte:02X}}}"),
+    }
+}
+
+/// A little endian word at `offset`.
+fn word(bytes: &[u8], offset: usize) -> Option<usize> {
+    let bytes = bytes.get(offset..offset + 2)?;
+    Some(usize::from(u16::from_le_bytes([bytes[0], bytes[1]])))
+}
+
+/// Sectors of a D64 image.
+struct Disk<'a>(&'a [u8]);
+
+impl Disk<'_> {
+    /// 256 bytes of a sector, tracks start at 1.
+    fn sector(&self, track: u8, sector: u8) -> Result<&[u8], LoadError> {
+        let invalid =
+            || LoadError::Malformed("D64", format!("invalid track {track} sector {sector}"));
+        if track == 0 || sector >= sectors(track) {
+            return Err(invalid());
+        }
+
+        let offset = self.offset(track, sector);
+        self.0.get(offset..offset + 256).ok_or_else(invalid)
+    }
+
+    /// Offset of a sector in the image.
+    fn offset(&self, track: u8, sector: u8) -> usize {
+        let index = (1..track)
+            .map(|track| usize::from(sectors(track)))
+            .sum::<usize>()
+            + usize::from(sector);
+        index * 256
+    }
+
+    /// Sectors linked from `track` and `sector`, the chain ends at track 0.
+    fn chain(&self, mut track: u8, mut sector: u8) -> Result<Vec<&[u8]>, LoadError> {
+        let mut sectors = vec![];
+        while track != 0 {
+            // a loop in the chain
+            if sectors.len() == self.0.len() / 256 {
+                return Err(LoadError::Malformed(
+                    "D64",
+                    "a sector chain loops".to_string(),
+                ));
+            }
+            let data = self.sector(track, sector)?;
+            (track, sector) = (data[0], data[1]);
+            sectors.push(data);
+        }
+
+        Ok(sectors)
+    }
+}
+
+/// Number of sectors of a D64 `track`.
+fn sectors(track: u8) -> u8 {
+    match track {
+        1..=17 => 21,
+        18..=24 => 19,
+        25..=30 => 18,
+        _ => 17,
+    }
+}
+
+/// Selects a `file` by name or the first one matching `filter`.
+fn select<T>(
+    files: Vec<(String, T)>,
+    file: Option<&str>,
+    filter: impl Fn(&(String, T)) -> bool,
+    format: &'static str,
+) -> Result<(String, T), LoadError> {
+    let names = files
+        .iter()
+        .filter(|file| filter(file))
+        .map(|(name, _)| name.clone())
+        .collect();
+    let found = match file {
+        Some(file) => files
+            .into_iter()
+            .filter(&filter)
+            .find(|(name, _)| name.eq_ignore_ascii_case(file)),
+        None => files.into_iter().find(&filter),
+    };
+
+    found.ok_or_else(|| match file {
+        Some(file) => LoadError::MissingFile(file.to_string(), names),
+        None => LoadError::Malformed(format, "no program files".to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `10 PRINT "{$9E}";:SYS(2069)` and `RTS` at 0x0815, quoted tokens are characters.
+    const PROGRAM: &[u8] = &[
+        0x01, 0x08, 0x13, 0x08, 0x0A, 0x00, 0x99, 0x22, 0x9E, 0x22, 0x3B, 0x3A, 0x9E, 0x28, 0x32,
+        0x30, 0x36, 0x39, 0x29, 0x00, 0x00, 0x00, 0x60,
+    ];
+
+    fn d64(files: &[(&[u8], u8, &[u8])]) -> Vec<u8> {
+        let mut disk = vec![0; D64_SIZE];
+        // the directory at track 18 sector 1, files from track 1
+        let directory = Disk(&[]).offset(18, 0);
+        disk[directory..directory + 2].copy_from_slice(&[18, 1]);
+        let directory = Disk(&[]).offset(18, 1);
+        for (index, (name, kind, contents)) in files.iter().enumerate() {
+            let entry = directory + index * 32;
+            disk[entry + 2..entry + 5].copy_from_slice(&[*kind, 1, index as u8]);
+            disk[entry + 5..entry + 0x15].fill(0xA0);
+            disk[entry + 5..entry + 5 + name.len()].copy_from_slice(name);
+
+            let sector = Disk(&[]).offset(1, index as u8);
+            disk[sector..sector + 2].copy_from_slice(&[0, contents.len() as u8 + 1]);
+            disk[sector + 2..sector + 2 + contents.len()].copy_from_slice(contents);
+        }
+
+        disk
+    }
+
+    #[test]
+    fn test_prg() {
+        let image = load_prg(PROGRAM).unwrap();
+
+        assert_eq!(
+            image.notes,
+            ["BASIC at 0x801", "10 PRINT\"{$9E}\";:SYS(2069)"]
+        );
+        assert_eq!(image.entries, [0x0815]);
+        assert_eq!(image.sections[0].address, 0x0815);
+        assert_eq!(image.sections[0].bytes, [0x60]);
+
+        // the SYS target is out of the program
+        let image = load_prg(&PROGRAM[..PROGRAM.len() - 1]).unwrap();
+        assert_eq!(image.notes, Vec::<String>::new());
+        assert!(image.entries.is_empty());
+        assert_eq!(image.sections[0].address, 0x0801);
+    }
+
+    #[test]
+    fn test_t64() {
+        let mut tape = b"C64S tape file".to_vec();
+        tape.resize(0x60, 0);
+        tape[0x22] = 1;
+        tape[0x40..0x46].copy_from_slice(&[1, 0x82, 0x00, 0xC0, 0x02, 0xC0]);
+        tape[0x48] = 0x60;
+        tape[0x50..0x60].copy_from_slice(b"GAME            ");
+        tape.extend([0xEA, 0x60]);
+
+        let image = load_t64(&tape, Some("game")).unwrap();
+        assert_eq!(image.sections[0].name, "GAME");
+        assert_eq!(image.sections[0].address, 0xC000);
+        assert_eq!(image.sections[0].bytes, [0xEA, 0x60]);
+    }
+
+    #[test]
+    fn test_d64() {
+        let disk = d64(&[
+            (b"README", 0x81, b"TEXT"),
+            (b"LOADER", 0x82, PROGRAM),
+            (b"GAME", 0x82, &[0x00, 0xC0, 0x60]),
+        ]);
+
+        assert_eq!(load_d64(&disk, None).unwrap().entries, [0x0815]);
+        assert_eq!(
+            load_d64(&disk, Some("GAME")).unwrap().sections[0].address,
+            0xC000
+        );
+        assert!(matches!(
+            load_d64(&disk, Some("README")),
+            Err(LoadError::MissingFile(name, files)) if name == "README" && files == ["LOADER", "GAME"]
+        ));
+    }
+}
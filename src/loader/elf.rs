@@ -0,0 +1,120 @@
+//! ELF executables, objects and shared libraries.
+
+use std::collections::HashMap;
+
+use goblin::elf::{
+    header::{machine_to_str, EM_386, EM_RISCV, EM_X86_64},
+    program_header::{PF_X, PT_LOAD},
+    section_header::{SHF_EXECINSTR, SHT_PROGBITS},
+    sym::{STB_LOCAL, STT_FUNC, STT_NOTYPE, STT_OBJECT},
+    Elf,
+};
+
+use crate::{ArchOptions, BitWidth, Scope, SymbolInfo};
+
+use super::{Image, LoadError, Section};
+
+const FORMAT: &str = "ELF";
+
+/// Loads executable sections, or executable segments if there are no section headers.
+pub(super) fn load(bytes: &[u8]) -> Result<Image, LoadError> {
+    let elf = Elf::parse(bytes).map_err(|e| LoadError::Malformed(FORMAT, e.to_string()))?;
+
+    let arch = match elf.header.e_machine {
+        EM_386 => ArchOptions::default()
+            .with_arch("x86")
+            .with_width(BitWidth::Bit32),
+        EM_X86_64 => ArchOptions::default()
+            .with_arch("x86")
+            .with_width(BitWidth::Bit64),
+        EM_RISCV if !elf.is_64 => ArchOptions::default()
+            .with_arch("riscv")
+            .with_width(BitWidth::Bit32),
+        // RV64 instructions are not decoded
+        EM_RISCV => {
+            return Err(LoadError::UnsupportedMachine(format!(
+                "{} 64-bit",
+                machine_to_str(EM_RISCV)
+            )))
+        }
+        machine => {
+            return Err(LoadError::UnsupportedMachine(
+                machine_to_str(machine).to_string(),
+            ))
+        }
+    };
+
+    let contents = |offset: u64, size: u64| {
+        let range =
+            usize::try_from(offset).ok()?..usize::try_from(offset.checked_add(size)?).ok()?;
+        bytes.get(range).map(<[u8]>::to_vec)
+    };
+    let mut sections = vec![];
+    for header in &elf.section_headers {
+        if header.sh_type != SHT_PROGBITS || header.sh_flags & u64::from(SHF_EXECINSTR) == 0 {
+            continue;
+        }
+        let name = elf.shdr_strtab.get_at(header.sh_name).unwrap_or_default();
+        let bytes = contents(header.sh_offset, header.sh_size).ok_or_else(|| {
+            LoadError::Malformed(FORMAT, format!("section {name} is out of the file"))
+        })?;
+
+        sections.push(Section {
+            name: name.to_string(),
+            address: header.sh_addr as usize,
+            bytes,
+        });
+    }
+    if elf.section_headers.is_empty() {
+        for header in &elf.program_headers {
+            if header.p_type != PT_LOAD || header.p_flags & PF_X == 0 {
+                continue;
+            }
+            let bytes = contents(header.p_offset, header.p_filesz).ok_or_else(|| {
+                LoadError::Malformed(FORMAT, "a segment is out of the file".to_string())
+            })?;
+
+            sections.push(Section {
+                name: "LOAD".to_string(),
+                address: header.p_vaddr as usize,
+                bytes,
+            });
+        }
+    }
+
+    let mut symbols = HashMap::new();
+    let tables = [(&elf.syms, &elf.strtab), (&elf.dynsyms, &elf.dynstrtab)];
+    for (table, strings) in tables {
+        for sym in table.iter() {
+            // undefined, e.g. imports, and section or file symbols
+            if sym.st_shndx == 0
+                || sym.st_value == 0
+                || ![STT_NOTYPE, STT_FUNC, STT_OBJECT].contains(&sym.st_type())
+            {
+                continue;
+            }
+            let Some(name) = strings.get_at(sym.st_name).filter(|name| !name.is_empty()) else {
+                continue;
+            };
+            let scope = match sym.st_bind() {
+                STB_LOCAL => Scope::Local,
+                _ => Scope::Global,
+            };
+
+            symbols
+                .entry(SymbolInfo::new(sym.st_value as usize, scope))
+                .or_insert_with(|| name.to_string());
+        }
+    }
+
+    Ok(Image {
+        arch,
+        sections,
+        symbols,
+        entries: (elf.entry != 0)
+            .then_some(elf.entry as usize)
+            .into_iter()
+            .collect(),
+        notes: vec![],
+    })
+}
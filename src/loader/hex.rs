@@ -0,0 +1,250 @@
+//! Intel HEX and Motorola S-record text files.
+
+use std::collections::BTreeMap;
+
+use super::{Image, LoadError, Section};
+
+/// Data of the records by address, later records overwrite earlier ones.
+type Memory = BTreeMap<usize, u8>;
+
+/// Loads Intel HEX data records, extended segment and linear addresses are applied.
+/// Data wraps around within a segment as the specification requires, but not within
+/// a linear address page.
+pub(super) fn load_hex(bytes: &[u8]) -> Result<Image, LoadError> {
+    const FORMAT: &str = "Intel HEX";
+
+    let mut memory = Memory::new();
+    let mut entries = vec![];
+    // the extended segment or linear address
+    let mut base = 0;
+    let mut segmented = true;
+    for (line, record) in lines(bytes, FORMAT)? {
+        let invalid = |reason: &str| LoadError::InvalidRecord(line, reason.to_string());
+        let record = record
+            .strip_prefix(':')
+            .ok_or_else(|| invalid("expected `:`"))?;
+        let record = decode(record).ok_or_else(|| invalid("expected hexadecimal digits"))?;
+        if record.len() < 5 || record.len() != usize::from(record[0]) + 5 {
+            return Err(invalid("the length does not match the data"));
+        }
+        if record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let address = usize::from(u16::from_be_bytes([record[1], record[2]]));
+        let data = &record[4..record.len() - 1];
+        let value = || {
+            data.iter()
+                .fold(0, |value, byte| value << 8 | usize::from(*byte))
+        };
+        match (record[3], data.len()) {
+            (0x00, _) => {
+                let mask = if segmented { 0xFFFF } else { usize::MAX };
+                memory.extend(
+                    data.iter()
+                        .enumerate()
+                        .map(|(offset, byte)| (base + ((address + offset) & mask), *byte)),
+                );
+            }
+            (0x01, _) => break,
+            (0x02, 2) => (base, segmented) = (value() << 4, true),
+            (0x04, 2) => (base, segmented) = (value() << 16, false),
+            // CS:IP
+            (0x03, 4) => entries.push((value() >> 16 << 4) + (value() & 0xFFFF)),
+            (0x05, 4) => entries.push(value()),
+            (0x02..=0x05, _) => return Err(invalid("unexpected data length")),
+            (kind, _) => return Err(invalid(&format!("unknown record type {kind:02X}"))),
+        }
+    }
+
+    Ok(image(FORMAT, &memory, entries))
+}
+
+/// Loads Motorola S-record data records, the header record is shown as a note.
+pub(super) fn load_srec(bytes: &[u8]) -> Result<Image, LoadError> {
+    const FORMAT: &str = "S-record";
+
+    let mut memory = Memory::new();
+    let mut entries = vec![];
+    let mut notes = vec![];
+    for (line, record) in lines(bytes, FORMAT)? {
+        let invalid = |reason: &str| LoadError::InvalidRecord(line, reason.to_string());
+        let (kind, record) = record
+            .strip_prefix('S')
+            .and_then(|record| record.split_at_checked(1))
+            .ok_or_else(|| invalid("expected `S` and the record type"))?;
+        let record = decode(record).ok_or_else(|| invalid("expected hexadecimal digits"))?;
+        if record.is_empty() || record.len() != usize::from(record[0]) + 1 {
+            return Err(invalid("the length does not match the data"));
+        }
+        if record.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0xFF {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let address_size = match kind {
+            "0" | "1" | "5" | "9" => 2,
+            "2" | "6" | "8" => 3,
+            "3" | "7" => 4,
+            kind => return Err(invalid(&format!("unknown record type S{kind}"))),
+        };
+        let data = record
+            .get(1 + address_size..record.len() - 1)
+            .ok_or_else(|| invalid("the record is shorter than its address"))?;
+        let address = record[1..=address_size]
+            .iter()
+            .fold(0, |value, byte| value << 8 | usize::from(*byte));
+        match kind {
+            "0" if !data.is_empty() => {
+                notes.push(format!("header {}", String::from_utf8_lossy(data)))
+            }
+            "1" | "2" | "3" => insert(&mut memory, address, data),
+            "7" | "8" | "9" => entries.push(address),
+            // the header without a name and record counts
+            _ => {}
+        }
+    }
+
+    let mut image = image(FORMAT, &memory, entries);
+    image.notes = notes;
+    Ok(image)
+}
+
+/// Non-empty lines of a text file with their 1-based numbers.
+fn lines<'a>(bytes: &'a [u8], format: &'static str) -> Result<Vec<(usize, &'a str)>, LoadError> {
+    let text =
+        std::str::from_utf8(bytes).map_err(|e| LoadError::Malformed(format, e.to_string()))?;
+
+    Ok(text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect())
+}
+
+/// Bytes of hexadecimal digit pairs.
+fn decode(digits: &str) -> Option<Vec<u8>> {
+    if !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return None;
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&digits[index..index + 2], 16).ok())
+        .collect()
+}
+
+fn insert(memory: &mut Memory, address: usize, data: &[u8]) {
+    memory.extend(
+        data.iter()
+            .enumerate()
+            .map(|(offset, byte)| (address + offset, *byte)),
+    );
+}
+
+/// Sections of the contiguous address ranges, the architecture is not known.
+fn image(format: &str, memory: &Memory, entries: Vec<usize>) -> Image {
+    let mut sections: Vec<Section> = vec![];
+    for (address, byte) in memory {
+        match sections.last_mut() {
+            Some(section) if section.address + section.bytes.len() == *address => {
+                section.bytes.push(*byte)
+            }
+            _ => sections.push(Section {
+                name: format.to_string(),
+                address: *address,
+                bytes: vec![*byte],
+            }),
+        }
+    }
+
+    Image {
+        sections,
+        entries,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(image: &Image) -> Vec<(usize, Vec<u8>)> {
+        image
+            .sections
+            .iter()
+            .map(|section| (section.address, section.bytes.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_hex() {
+        let image = load_hex(
+            b":03000000A90160F3\n\
+              :020000040001F9\n\
+              :02100000EA60A4\n\
+              :0400000500010000F6\n\
+              :00000001FF\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            ranges(&image),
+            [(0, vec![0xA9, 0x01, 0x60]), (0x11000, vec![0xEA, 0x60])]
+        );
+        assert_eq!(image.entries, [0x10000]);
+
+        // the offset wraps around within the segment 1000h
+        let image = load_hex(
+            b":020000021000EC\n\
+              :02FFFF00EA60B6\n",
+        )
+        .unwrap();
+        assert_eq!(
+            ranges(&image),
+            [(0x10000, vec![0x60]), (0x1FFFF, vec![0xEA])]
+        );
+    }
+
+    #[test]
+    fn test_srec() {
+        let image = load_srec(
+            b"S00600004844521B\n\
+              S1068000A901606F\n\
+              S1058010EA6020\n\
+              S90380007C\n",
+        )
+        .unwrap();
+        assert_eq!(
+            ranges(&image),
+            [(0x8000, vec![0xA9, 0x01, 0x60]), (0x8010, vec![0xEA, 0x60])]
+        );
+        assert_eq!(image.entries, [0x8000]);
+        assert_eq!(image.notes, ["header HDR"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |result: Result<Image, LoadError>| match result {
+            Err(LoadError::InvalidRecord(line, reason)) => (line, reason),
+            result => panic!("unexpected {result:?}"),
+        };
+
+        assert_eq!(
+            error(load_hex(b":03000000A90160F3\n\n:03000000A90160F4\n")),
+            (3, "checksum mismatch".to_string())
+        );
+        assert_eq!(
+            error(load_hex(b"03000000A90160F3\n")),
+            (1, "expected `:`".to_string())
+        );
+        assert_eq!(
+            error(load_srec(b"S1068000A901606E\n")),
+            (1, "checksum mismatch".to_string())
+        );
+        assert_eq!(
+            error(load_srec(b"S4058010EA6020\n")),
+            (1, "unknown record type S4".to_string())
+        );
+    }
+}
@@ -0,0 +1,380 @@
+//! Executable file formats, see [Image].
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    ArchOptions, AssemblerOutput, DecodedInstruction, DisasmError, Discovery, DynDisassembler,
+    DynError, ErrorDetails, ErrorKind, ShowAddress, SymbolInfo,
+};
+
+#[cfg(feature = "commodore")]
+mod commodore;
+#[cfg(feature = "elf")]
+mod elf;
+#[cfg(feature = "hex")]
+mod hex;
+#[cfg(feature = "nes")]
+mod nes;
+#[cfg(feature = "pe")]
+mod pe;
+
+/// Container format of the input bytes.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub enum Input {
+    /// Code loaded at [ShowAddress::Start].
+    #[default]
+    Raw,
+    /// An ELF executable, object or shared library.
+    #[cfg(feature = "elf")]
+    Elf,
+    /// A PE/COFF executable or DLL.
+    #[cfg(feature = "pe")]
+    Pe,
+    /// A Commodore program file starting with its load address.
+    #[cfg(feature = "commodore")]
+    Prg,
+    /// A program file of a C64 tape image.
+    #[cfg(feature = "commodore")]
+    T64 {
+        /// Name of the file, the first program if omitted.
+        #[serde(default)]
+        file: Option<String>,
+    },
+    /// A program file of a 1541 disk image.
+    #[cfg(feature = "commodore")]
+    D64 {
+        /// Name of the file, the first program if omitted.
+        #[serde(default)]
+        file: Option<String>,
+    },
+    /// An iNES or NES 2.0 ROM image.
+    #[cfg(feature = "nes")]
+    Nes,
+    /// An Intel HEX file.
+    #[cfg(feature = "hex")]
+    Hex,
+    /// A Motorola S-record file.
+    #[cfg(feature = "hex")]
+    Srec,
+}
+
+/// Code and symbols of an executable file.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Image {
+    /// Architecture parameters detected from the file.
+    pub arch: ArchOptions,
+    /// Parts of the file to disassemble.
+    pub sections: Vec<Section>,
+    pub symbols: HashMap<SymbolInfo, String>,
+    /// Entry point addresses, e.g. of the program and its interrupt handlers.
+    pub entries: Vec<usize>,
+    /// Comments shown before the sections, e.g. a BASIC program starting the code.
+    pub notes: Vec<String>,
+}
+
+/// Code loaded at an address.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Section {
+    pub name: String,
+    /// Address of the first byte.
+    pub address: usize,
+    pub bytes: Vec<u8>,
+}
+
+impl Section {
+    /// Address after the last byte, fails if the section overflows the address space.
+    pub fn end(&self) -> Result<usize, LoadError> {
+        self.address.checked_add(self.bytes.len()).ok_or_else(|| {
+            LoadError::Malformed(
+                "executable",
+                format!(
+                    "section {} at {:#X} overflows the address space",
+                    self.name, self.address
+                ),
+            )
+        })
+    }
+}
+
+/// An input file error.
+#[derive(Error, Debug)]
+pub enum LoadError {
+    /// Contains the input format and the reason.
+    #[error("Malformed {0} file: {1}")]
+    Malformed(&'static str, String),
+    /// Contains the machine the file is built for.
+    #[error("Unsupported machine: {0}")]
+    UnsupportedMachine(String),
+    /// Contains the requested name and the names of the files in the container.
+    #[error("File not found: {0}")]
+    MissingFile(String, Vec<String>),
+    /// Contains the 1-based line number and the reason.
+    #[error("Invalid record at line {0}: {1}")]
+    InvalidRecord(usize, String),
+    /// Contains the iNES mapper number.
+    #[cfg(feature = "nes")]
+    #[error("Unsupported mapper: {0}")]
+    UnsupportedMapper(u16),
+}
+
+impl ErrorDetails for LoadError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Malformed(..) | Self::MissingFile(..) | Self::InvalidRecord(..) => {
+                ErrorKind::InvalidRequest
+            }
+            Self::UnsupportedMachine(_) => ErrorKind::Unsupported,
+            #[cfg(feature = "nes")]
+            Self::UnsupportedMapper(_) => ErrorKind::Unsupported,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Malformed(..) => "malformed_input",
+            Self::UnsupportedMachine(_) => "unsupported_machine",
+            Self::MissingFile(..) => "missing_file",
+            Self::InvalidRecord(..) => "invalid_record",
+            #[cfg(feature = "nes")]
+            Self::UnsupportedMapper(_) => "unsupported_mapper",
+        }
+    }
+
+    fn field(&self) -> Option<&'static str> {
+        match self {
+            Self::Malformed(..) | Self::UnsupportedMachine(_) | Self::InvalidRecord(..) => {
+                Some("bytes")
+            }
+            Self::MissingFile(..) => Some("input.file"),
+            #[cfg(feature = "nes")]
+            Self::UnsupportedMapper(_) => Some("bytes"),
+        }
+    }
+
+    fn allowed(&self) -> Vec<String> {
+        match self {
+            Self::Malformed(..) | Self::UnsupportedMachine(_) | Self::InvalidRecord(..) => {
+                vec![]
+            }
+            Self::MissingFile(_, files) => files.clone(),
+            #[cfg(feature = "nes")]
+            Self::UnsupportedMapper(_) => nes::MAPPERS
+                .iter()
+                .map(|(mapper, _)| mapper.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Input {
+    /// Lower case names of the enabled formats.
+    pub const NAMES: &'static [&'static str] = &[
+        "raw",
+        #[cfg(feature = "elf")]
+        "elf",
+        #[cfg(feature = "pe")]
+        "pe",
+        #[cfg(feature = "commodore")]
+        "prg",
+        #[cfg(feature = "commodore")]
+        "t64",
+        #[cfg(feature = "commodore")]
+        "d64",
+        #[cfg(feature = "nes")]
+        "nes",
+        #[cfg(feature = "hex")]
+        "hex",
+        #[cfg(feature = "hex")]
+        "srec",
+    ];
+
+    /// Sets the name of the file to load from a container, other formats are not changed.
+    #[cfg_attr(not(feature = "commodore"), allow(unused_mut, unused_variables))]
+    pub fn with_file(mut self, name: Option<String>) -> Self {
+        #[cfg(feature = "commodore")]
+        if let Self::T64 { file } | Self::D64 { file } = &mut self {
+            *file = name;
+        }
+        self
+    }
+
+    /// Recognizes the format of `bytes` by its magic, [Input::Raw] if none matches
+    /// or the file does not load. A program file is only recognized by its BASIC stub.
+    pub fn detect(bytes: &[u8]) -> Self {
+        let candidates: Vec<(Self, bool)> = vec![
+            #[cfg(feature = "elf")]
+            (Self::Elf, bytes.starts_with(b"\x7FELF")),
+            #[cfg(feature = "pe")]
+            (Self::Pe, bytes.starts_with(b"MZ")),
+            #[cfg(feature = "nes")]
+            (Self::Nes, bytes.starts_with(b"NES\x1A")),
+            #[cfg(feature = "commodore")]
+            (Self::T64 { file: None }, bytes.starts_with(b"C64")),
+            #[cfg(feature = "commodore")]
+            (
+                Self::D64 { file: None },
+                commodore::D64_SIZES.contains(&bytes.len()),
+            ),
+            #[cfg(feature = "hex")]
+            (Self::Hex, bytes.starts_with(b":")),
+            #[cfg(feature = "hex")]
+            (Self::Srec, bytes.starts_with(b"S")),
+        ];
+
+        let input = candidates
+            .into_iter()
+            .find(|(input, matches)| *matches && input.load(bytes).is_ok())
+            .map(|(input, _)| input);
+        #[cfg(feature = "commodore")]
+        let input = input.or_else(|| {
+            commodore::load_prg(bytes)
+                .is_ok_and(|image| !image.entries.is_empty())
+                .then_some(Self::Prg)
+        });
+
+        input.unwrap_or(Self::Raw)
+    }
+
+    /// Loads `bytes` of this format, [None] for [Input::Raw].
+    #[cfg_attr(
+        not(any(
+            feature = "elf",
+            feature = "pe",
+            feature = "commodore",
+            feature = "nes",
+            feature = "hex"
+        )),
+        allow(unused_variables)
+    )]
+    pub fn load(&self, bytes: &[u8]) -> Result<Option<Image>, LoadError> {
+        match self {
+            Self::Raw => Ok(None),
+            #[cfg(feature = "elf")]
+            Self::Elf => elf::load(bytes).map(Some),
+            #[cfg(feature = "pe")]
+            Self::Pe => pe::load(bytes).map(Some),
+            #[cfg(feature = "commodore")]
+            Self::Prg => commodore::load_prg(bytes).map(Some),
+            #[cfg(feature = "commodore")]
+            Self::T64 { file } => commodore::load_t64(bytes, file.as_deref()).map(Some),
+            #[cfg(feature = "commodore")]
+            Self::D64 { file } => commodore::load_d64(bytes, file.as_deref()).map(Some),
+            #[cfg(feature = "nes")]
+            Self::Nes => nes::load(bytes).map(Some),
+            #[cfg(feature = "hex")]
+            Self::Hex => hex::load_hex(bytes).map(Some),
+            #[cfg(feature = "hex")]
+            Self::Srec => hex::load_srec(bytes).map(Some),
+        }
+    }
+}
+
+impl Image {
+    /// Disassembles every section after a header comment, notes go first and unloaded
+    /// addresses between sections are shown as gaps. Symbols of the image are added
+    /// to the symbol table of `format`, addresses are shown unless [ShowAddress::None].
+    pub fn disassemble(
+        &self,
+        disasm: &dyn DynDisassembler,
+        format: &AssemblerOutput,
+    ) -> Result<Vec<String>, DisasmError<DynError>> {
+        let mut lines: Vec<_> = self
+            .notes
+            .iter()
+            .map(|note| disasm.comment_dyn(note))
+            .collect();
+        let mut end = None;
+        for section in &self.sections {
+            if let Some(end) = end.filter(|end| *end < section.address) {
+                lines.push(
+                    disasm
+                        .comment_dyn(&format!("gap at {end:#X}, {} bytes", section.address - end)),
+                );
+            }
+            end = Some(section.end().map_err(DisasmError::Load)?);
+            lines.push(disasm.comment_dyn(&format!(
+                "section {} at {:#X}, {} bytes",
+                section.name,
+                section.address,
+                section.bytes.len()
+            )));
+            let format = self.format(section, format).map_err(DisasmError::Load)?;
+            lines.extend(disasm.disassemble_dyn(&section.bytes, &format)?);
+        }
+
+        Ok(lines)
+    }
+
+    /// Decodes every section, see [Image::disassemble].
+    pub fn decode(
+        &self,
+        disasm: &dyn DynDisassembler,
+        format: &AssemblerOutput,
+    ) -> Result<Vec<DecodedInstruction>, DisasmError<DynError>> {
+        let mut instructions = vec![];
+        for section in &self.sections {
+            let format = self.format(section, format).map_err(DisasmError::Load)?;
+            instructions.extend(disasm.decode_dyn(&section.bytes, &format)?);
+        }
+
+        Ok(instructions)
+    }
+
+    /// Options of a `section`, names of `format` take precedence. Recursive discovery
+    /// without entries starts from the entry points in the section.
+    fn format(
+        &self,
+        section: &Section,
+        format: &AssemblerOutput,
+    ) -> Result<AssemblerOutput, LoadError> {
+        let address = match format.address() {
+            ShowAddress::None => ShowAddress::None,
+            ShowAddress::Start(_) => ShowAddress::Start(section.address),
+        };
+        let mut format = format.clone().with_addresses(address);
+        let range = section.address..section.end()?;
+        if matches!(format.discovery(), Discovery::Recursive(entries) if entries.is_empty()) {
+            let entries = self
+                .entries
+                .iter()
+                .copied()
+                .filter(|entry| range.contains(entry))
+                .collect();
+            format = format.with_discovery(Discovery::Recursive(entries));
+        }
+        if self.symbols.is_empty() {
+            return Ok(format);
+        }
+
+        let mut table = self.symbols.clone();
+        table.extend(
+            format
+                .symbol_table()
+                .into_iter()
+                .flatten()
+                .map(|(k, v)| (*k, v.clone())),
+        );
+        Ok(format.with_symbol_table(table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_section_end() {
+        let mut section = Section {
+            name: "text".to_string(),
+            address: 0x1000,
+            bytes: vec![0; 0x10],
+        };
+        assert_eq!(section.end().unwrap(), 0x1010);
+
+        section.address = usize::MAX - 1;
+        assert!(matches!(section.end(), Err(LoadError::Malformed(..))));
+    }
+}
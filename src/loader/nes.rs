@@ -0,0 +1,235 @@
+//! iNES and NES 2.0 ROM images.
+
+use crate::ArchOptions;
+
+use super::{Image, LoadError, Section};
+
+const FORMAT: &str = "iNES";
+
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+/// CPU address of a trainer.
+const TRAINER_ADDRESS: usize = 0x7000;
+
+/// Supported mappers and their common names.
+pub(super) const MAPPERS: [(u16, &str); 5] = [
+    (0, "NROM"),
+    (1, "MMC1"),
+    (2, "UxROM"),
+    (3, "CNROM"),
+    (7, "AxROM"),
+];
+
+/// Names of the interrupt vectors at the end of the address space.
+const VECTORS: [&str; 3] = ["NMI", "RESET", "IRQ"];
+
+/// Loads 16K PRG-ROM banks at the addresses they are mapped to after power on
+/// or by bank switching, CHR-ROM is skipped. Interrupt vectors of the last bank
+/// are the entry points.
+pub(super) fn load(bytes: &[u8]) -> Result<Image, LoadError> {
+    let malformed = |reason: &str| LoadError::Malformed(FORMAT, reason.to_string());
+    let header = bytes
+        .get(..HEADER_SIZE)
+        .filter(|header| header.starts_with(b"NES\x1A"))
+        .ok_or_else(|| malformed("no iNES header"))?;
+
+    let nes2 = header[7] & 0x0C == 0x08;
+    let mut mapper = u16::from(header[6] >> 4 | header[7] & 0xF0);
+    if nes2 {
+        mapper |= u16::from(header[8] & 0x0F) << 8;
+    }
+    let prg_size = rom_size(header[4], nes2.then_some(header[9] & 0x0F), BANK_SIZE)
+        .ok_or_else(|| malformed("the PRG-ROM size is out of range"))?;
+    let chr_size = rom_size(header[5], nes2.then_some(header[9] >> 4), CHR_BANK_SIZE)
+        .ok_or_else(|| malformed("the CHR-ROM size is out of range"))?;
+    let banks = prg_size / BANK_SIZE;
+    if banks == 0 || !prg_size.is_multiple_of(BANK_SIZE) {
+        return Err(malformed("the PRG-ROM is not made of 16K banks"));
+    }
+
+    let (_, name) = MAPPERS
+        .iter()
+        .find(|(number, _)| *number == mapper)
+        .ok_or(LoadError::UnsupportedMapper(mapper))?;
+
+    let mut offset = HEADER_SIZE;
+    let mut sections = vec![];
+    if header[6] & 0x04 != 0 {
+        let trainer = bytes
+            .get(offset..offset + TRAINER_SIZE)
+            .ok_or_else(|| malformed("the trainer is out of the file"))?;
+        sections.push(Section {
+            name: "trainer".to_string(),
+            address: TRAINER_ADDRESS,
+            bytes: trainer.to_vec(),
+        });
+        offset += TRAINER_SIZE;
+    }
+    let prg = offset
+        .checked_add(prg_size)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| malformed("the PRG-ROM is out of the file"))?;
+    // the bank count is only bounded once the PRG-ROM is known to fit in the file
+    let (addresses, fixed) = layout(mapper, banks);
+    for (bank, (bytes, address)) in prg.chunks(BANK_SIZE).zip(addresses).enumerate() {
+        sections.push(Section {
+            name: format!("PRG bank {bank}"),
+            address,
+            bytes: bytes.to_vec(),
+        });
+    }
+
+    let kind = match nes2 {
+        true => "NES 2.0",
+        false => "iNES",
+    };
+    let mut notes = vec![format!(
+        "{kind} mapper {mapper} ({name}), {banks} PRG-ROM banks"
+    )];
+    if chr_size > 0 {
+        notes.push(format!("CHR-ROM of {chr_size} bytes is skipped"));
+    }
+
+    // the vectors at 0xFFFA of the last bank
+    let vectors: Vec<_> = prg[prg_size - 6..]
+        .chunks(2)
+        .map(|vector| usize::from(u16::from_le_bytes([vector[0], vector[1]])))
+        .collect();
+    notes.push(
+        VECTORS
+            .iter()
+            .zip(&vectors)
+            .map(|(name, address)| format!("{name} {address:#X}"))
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    // handlers in switchable banks depend on the selected bank
+    let mut entries: Vec<_> = vectors.into_iter().filter(|v| *v >= fixed).collect();
+    entries.sort_unstable();
+    entries.dedup();
+
+    Ok(Image {
+        arch: ArchOptions::default().with_arch("mos6502"),
+        sections,
+        symbols: Default::default(),
+        entries,
+        notes,
+    })
+}
+
+/// Size of a ROM of `units` of `unit` bytes. NES 2.0 adds the most significant
+/// nibble `msb`, 0xF selects the exponent-multiplier notation.
+/// [None] if the size does not fit in `usize`.
+fn rom_size(units: u8, msb: Option<u8>, unit: usize) -> Option<usize> {
+    match msb {
+        Some(0x0F) => {
+            let multiplier = usize::from(units & 0x03) * 2 + 1;
+            1usize
+                .checked_shl(u32::from(units >> 2))?
+                .checked_mul(multiplier)
+        }
+        Some(msb) => (usize::from(msb) << 8 | usize::from(units)).checked_mul(unit),
+        None => usize::from(units).checked_mul(unit),
+    }
+}
+
+/// CPU addresses of `banks` of a supported `mapper` and the lowest address
+/// an interrupt handler is found at regardless of the selected bank.
+fn layout(mapper: u16, banks: usize) -> (Vec<usize>, usize) {
+    let last = banks - 1;
+    match mapper {
+        // MMC1 fixes the last bank at 0xC000 after power on, as UxROM does
+        1 | 2 => (
+            (0..banks)
+                .map(|bank| if bank == last { 0xC000 } else { 0x8000 })
+                .collect(),
+            0xC000,
+        ),
+        // a 16K ROM is mirrored
+        _ if banks == 1 => (vec![0xC000], 0x8000),
+        _ => {
+            let addresses = (0..banks)
+                .map(|bank| 0x8000 + bank % 2 * BANK_SIZE)
+                .collect();
+            // AxROM switches 32K banks, games repeat the handlers in the upper half of each
+            let fixed = if mapper == 7 { 0xC000 } else { 0x8000 };
+            (addresses, fixed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image with a `mapper` and `banks` filled with their numbers,
+    /// the vectors point to 0xC000, 0xC010 and 0x8000.
+    fn rom(mapper: u8, banks: u8) -> Vec<u8> {
+        let mut bytes = b"NES\x1A".to_vec();
+        bytes.extend([banks, 1, mapper << 4, mapper & 0xF0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        for bank in 0..banks {
+            bytes.extend([bank; BANK_SIZE]);
+        }
+        let vectors = bytes.len() - 6;
+        bytes[vectors..].copy_from_slice(&[0x10, 0xC0, 0x00, 0xC0, 0x00, 0x80]);
+        bytes.extend([0xFF; CHR_BANK_SIZE]);
+        bytes
+    }
+
+    fn addresses(image: &Image) -> Vec<usize> {
+        image
+            .sections
+            .iter()
+            .map(|section| section.address)
+            .collect()
+    }
+
+    #[test]
+    fn test_mappers() {
+        let image = load(&rom(2, 4)).unwrap();
+        assert_eq!(addresses(&image), [0x8000, 0x8000, 0x8000, 0xC000]);
+        assert_eq!(image.sections[3].bytes[0], 3);
+        assert_eq!(image.entries, [0xC000, 0xC010]);
+        assert_eq!(
+            image.notes,
+            [
+                "iNES mapper 2 (UxROM), 4 PRG-ROM banks",
+                "CHR-ROM of 8192 bytes is skipped",
+                "NMI 0xC010, RESET 0xC000, IRQ 0x8000",
+            ]
+        );
+
+        let image = load(&rom(0, 2)).unwrap();
+        assert_eq!(addresses(&image), [0x8000, 0xC000]);
+        assert_eq!(image.entries, [0x8000, 0xC000, 0xC010]);
+        assert_eq!(addresses(&load(&rom(0, 1)).unwrap()), [0xC000]);
+        assert_eq!(
+            addresses(&load(&rom(7, 4)).unwrap()),
+            [0x8000, 0xC000, 0x8000, 0xC000]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(
+            load(&rom(4, 2)),
+            Err(LoadError::UnsupportedMapper(4))
+        ));
+        assert!(matches!(
+            load(&rom(0, 2)[..0x4000]),
+            Err(LoadError::Malformed(FORMAT, _))
+        ));
+
+        // NES 2.0 exponent-multiplier sizes far beyond the file or `usize`
+        for size in [0xC0, 0xFD] {
+            let mut bytes = rom(0, 2);
+            bytes[4] = size;
+            bytes[7] |= 0x08;
+            bytes[9] = 0x0F;
+            assert!(matches!(load(&bytes), Err(LoadError::Malformed(FORMAT, _))));
+        }
+    }
+}
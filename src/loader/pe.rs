@@ -0,0 +1,119 @@
+//! PE/COFF executables and DLLs.
+
+use std::collections::HashMap;
+
+use goblin::pe::{
+    header::{machine_to_str, COFF_MACHINE_X86, COFF_MACHINE_X86_64},
+    section_table::{IMAGE_SCN_CNT_CODE, IMAGE_SCN_MEM_EXECUTE},
+    PE,
+};
+
+use crate::{ArchOptions, BitWidth, Scope, SymbolInfo};
+
+use super::{Image, LoadError, Section};
+
+const FORMAT: &str = "PE";
+
+/// Name of the entry point if it is not exported.
+const ENTRY: &str = "entry";
+
+/// Loads executable sections at `ImageBase + RVA`. Import address table slots are named
+/// `DLL!function`, or `DLL!#ordinal` for imports by ordinal.
+pub(super) fn load(bytes: &[u8]) -> Result<Image, LoadError> {
+    let pe = PE::parse(bytes).map_err(|e| LoadError::Malformed(FORMAT, e.to_string()))?;
+
+    let width = match pe.header.coff_header.machine {
+        COFF_MACHINE_X86 => BitWidth::Bit32,
+        COFF_MACHINE_X86_64 => BitWidth::Bit64,
+        machine => {
+            return Err(LoadError::UnsupportedMachine(
+                machine_to_str(machine).to_string(),
+            ))
+        }
+    };
+    let address = |rva: usize| {
+        (pe.image_base as usize).checked_add(rva).ok_or_else(|| {
+            LoadError::Malformed(FORMAT, format!("RVA {rva:#X} overflows the address space"))
+        })
+    };
+
+    let mut sections = vec![];
+    for header in &pe.sections {
+        if header.characteristics & (IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE) == 0 {
+            continue;
+        }
+        let name = header.name().unwrap_or_default();
+        // the raw data is padded to the file alignment
+        let size = match header.virtual_size {
+            0 => header.size_of_raw_data,
+            size => size.min(header.size_of_raw_data),
+        };
+        let start = header.pointer_to_raw_data as usize;
+        let range = start..start.saturating_add(size as usize);
+        let bytes = bytes.get(range).ok_or_else(|| {
+            LoadError::Malformed(FORMAT, format!("section {name} is out of the file"))
+        })?;
+
+        sections.push(Section {
+            name: name.to_string(),
+            address: address(header.virtual_address as usize)?,
+            bytes: bytes.to_vec(),
+        });
+    }
+
+    let mut symbols = HashMap::new();
+    for import in &pe.imports {
+        let name = match import.rva {
+            0 => format!("{}!#{}", import.dll, import.ordinal),
+            _ => format!("{}!{}", import.dll, import.name),
+        };
+        symbols.insert(
+            SymbolInfo::new(address(import.offset)?, Scope::Global),
+            name,
+        );
+    }
+    for export in &pe.exports {
+        if let Some(name) = export.name.filter(|_| export.reexport.is_none()) {
+            symbols.insert(
+                SymbolInfo::new(address(export.rva)?, Scope::Global),
+                name.to_string(),
+            );
+        }
+    }
+    let entry = match pe.entry {
+        0 => None,
+        entry => Some(address(entry as usize)?),
+    };
+    if let Some(entry) = entry {
+        symbols
+            .entry(SymbolInfo::new(entry, Scope::Global))
+            .or_insert_with(|| ENTRY.to_string());
+    }
+
+    Ok(Image {
+        arch: ArchOptions::default().with_arch("x86").with_width(width),
+        sections,
+        symbols,
+        entries: entry.into_iter().collect(),
+        notes: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_base_overflow() {
+        let mut bytes = std::fs::read("test-bin/x86/pe64.bin").unwrap();
+        // ImageBase of the PE32+ optional header
+        let header = u32::from_le_bytes(bytes[0x3C..0x40].try_into().unwrap()) as usize;
+        let image_base = header + 24 + 24;
+        bytes[image_base..image_base + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        let Err(LoadError::Malformed(FORMAT, reason)) = load(&bytes) else {
+            panic!("the image base is accepted");
+        };
+        assert!(reason.ends_with("overflows the address space"), "{reason}");
+    }
+}
@@ -1,28 +1,90 @@
-use std::net::{Ipv4Addr, SocketAddrV4};
+use std::{io, net::SocketAddr, process::ExitCode, sync::Arc};
 
-use axum::{routing::post, Router};
-use tokio::net::TcpListener;
+use axum::extract::DefaultBodyLimit;
+use clap::Parser;
+use thiserror::Error;
+use tokio::{net::TcpListener, runtime};
 use tracing::info;
+use tracing_subscriber::{filter::ParseError, EnvFilter};
 
-use server::{MOS6502_ENDPOINT, RISC_V_ENDPOINT, X86_ENDPOINT};
+use config::{Config, LogFormat};
+use disassembler::{server, DisassemblerRegistry};
 
-mod server;
+mod config;
 
-#[tokio::main]
-async fn main() {
-    tracing_subscriber::fmt().init();
+/// A service startup error.
+#[derive(Error, Debug)]
+enum StartupError {
+    #[error("Invalid log filter: {0}")]
+    LogFilter(ParseError),
+    #[error("Failed to start the runtime")]
+    Runtime(#[source] io::Error),
+    #[error("Failed to bind to {0}")]
+    Bind(SocketAddr, #[source] io::Error),
+    #[error("Server error")]
+    Serve(#[source] io::Error),
+}
+
+fn main() -> ExitCode {
+    let config = Config::parse();
+
+    match run(config) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // print the whole chain, e.g. "Failed to bind to ...: Address already in use"
+            let mut message = e.to_string();
+            let mut source = std::error::Error::source(&e);
+            while let Some(cause) = source {
+                message += &format!(": {cause}");
+                source = cause.source();
+            }
+
+            eprintln!("error: {message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(config: Config) -> Result<(), StartupError> {
+    init_logging(&config)?;
+
+    let mut runtime = runtime::Builder::new_multi_thread();
+    if let Some(threads) = config.worker_threads {
+        runtime.worker_threads(threads);
+    }
+    let runtime = runtime
+        .enable_all()
+        .build()
+        .map_err(StartupError::Runtime)?;
 
-    // such separation allows to introduce conflicting and target-specific options if needed
-    let routes = Router::new()
-        .route(MOS6502_ENDPOINT, post(server::handle_mos6502))
-        .route(X86_ENDPOINT, post(server::handle_x86))
-        .route(RISC_V_ENDPOINT, post(server::handle_risc_v));
+    runtime.block_on(serve(config))
+}
+
+fn init_logging(config: &Config) -> Result<(), StartupError> {
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(&config.log).map_err(StartupError::LogFilter)?);
 
-    let addr = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 9999);
-    let listener = TcpListener::bind(addr).await.unwrap();
+    match config.log_format {
+        LogFormat::Full => subscriber.init(),
+        LogFormat::Compact => subscriber.compact().init(),
+        LogFormat::Pretty => subscriber.pretty().init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+
+    Ok(())
+}
+
+async fn serve(config: Config) -> Result<(), StartupError> {
+    let routes = server::router(Arc::new(DisassemblerRegistry::builtin()))
+        .layer(DefaultBodyLimit::max(config.max_body_size));
+
+    let addr = config.socket_addr();
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| StartupError::Bind(addr, e))?;
     info!("{:<15} - {:?}\n", "LISTENING", listener.local_addr());
 
     axum::serve(listener, routes.into_make_service())
         .await
-        .unwrap();
+        .map_err(StartupError::Serve)
 }
@@ -1,16 +1,69 @@
-use std::str::FromStr;
+//! HTTP interface of the disassemblers.
+
+use std::{error::Error as StdError, sync::Arc};
 
 use axum::{
+    async_trait,
+    extract::{FromRequest, Path, Request, State},
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
-    Json,
+    routing::{get, post},
+    Json, Router,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use disassembler::{mos6502, risc_v, x86, AssemblerOutput, BitWidth, DisasmError, Disassembler};
+use crate::{
+    call_graph, cfg, diff, search, xrefs, ArchOptions, AssemblerOutput, CallGraph, Candidate,
+    Capabilities, DecodedInstruction, DisasmError, Disassembler, DisassemblerRegistry,
+    DynDisassembler, DynError, ErrorBody, ErrorDetails, ErrorKind, Image, Input, Match, Pattern,
+    SymbolError, SymbolFile, Xref,
+};
 
+#[cfg(feature = "x86")]
 pub const X86_ENDPOINT: &str = "/x86";
+#[cfg(feature = "mos6502")]
 pub const MOS6502_ENDPOINT: &str = "/mos6502";
+#[cfg(feature = "riscv")]
 pub const RISC_V_ENDPOINT: &str = "/risc_v";
+pub const ARCHITECTURES_ENDPOINT: &str = "/architectures";
+/// Dispatches to a [DisassemblerRegistry] entry by [ArchOptions::arch].
+pub const DISASSEMBLE_ENDPOINT: &str = "/disassemble";
+/// Dispatches to a [DisassemblerRegistry] entry by the `arch` path segment.
+pub const DISASSEMBLE_ARCH_ENDPOINT: &str = "/disassemble/:arch";
+/// Builds a control flow graph, dispatches like [DISASSEMBLE_ENDPOINT].
+pub const CFG_ENDPOINT: &str = "/cfg";
+/// Detects functions and builds a call graph, dispatches like [DISASSEMBLE_ENDPOINT].
+pub const FUNCTIONS_ENDPOINT: &str = "/functions";
+/// Ranks the architectures the bytes may be code of.
+pub const DETECT_ENDPOINT: &str = "/detect";
+/// Compares the instructions of two versions of the bytes, dispatches like [DISASSEMBLE_ENDPOINT].
+pub const DIFF_ENDPOINT: &str = "/diff";
+/// Finds instruction sequences matching a [Pattern], dispatches like [DISASSEMBLE_ENDPOINT].
+pub const SEARCH_ENDPOINT: &str = "/search";
+
+/// Builds the service router, disassemblers of the generic endpoints are taken from `registry`.
+pub fn router(registry: Arc<DisassemblerRegistry>) -> Router {
+    let routes = Router::new()
+        .route(ARCHITECTURES_ENDPOINT, get(handle_architectures))
+        .route(DISASSEMBLE_ENDPOINT, post(handle_disassemble))
+        .route(DISASSEMBLE_ARCH_ENDPOINT, post(handle_disassemble_arch))
+        .route(CFG_ENDPOINT, post(handle_cfg))
+        .route(FUNCTIONS_ENDPOINT, post(handle_functions))
+        .route(DETECT_ENDPOINT, post(handle_detect))
+        .route(DIFF_ENDPOINT, post(handle_diff))
+        .route(SEARCH_ENDPOINT, post(handle_search))
+        .fallback(handle_not_found);
+
+    // such separation allows to introduce conflicting and target-specific options if needed
+    #[cfg(feature = "mos6502")]
+    let routes = routes.route(MOS6502_ENDPOINT, post(handle::<crate::mos6502::Mos6502>));
+    #[cfg(feature = "x86")]
+    let routes = routes.route(X86_ENDPOINT, post(handle::<crate::x86::X86>));
+    #[cfg(feature = "riscv")]
+    let routes = routes.route(RISC_V_ENDPOINT, post(handle::<crate::risc_v::RiscV>));
+
+    routes.with_state(registry)
+}
 
 /// Common input to the disassembly service.
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,350 +75,443 @@ pub struct Payload {
     arch: ArchOptions,
     /// Output disassembly formatting options.
     format: AssemblerOutput,
+    /// Container format of [Payload::bytes], its architecture fills in the missing [ArchOptions].
+    #[serde(default)]
+    input: Input,
+    /// A tool-native symbol file added to [AssemblerOutput::symbol_table].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    symbols: Option<SymbolFile>,
 }
 
-/// Architecture parameters.
-/// Option can be omitted (and will not be validated) for architectures that support only 1 variant
-/// or do not support it at all.
-#[derive(Debug, Default, Serialize, Deserialize)]
-pub struct ArchOptions {
-    /// Target architecture bit width.
-    width: Option<BitWidth>,
-    /// Output disassembly syntax.
-    syntax: Option<String>,
+/// Input to [CFG_ENDPOINT].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CfgPayload {
+    #[serde(flatten)]
+    payload: Payload,
+    /// Function entry address.
+    entry: usize,
+    /// Response format.
+    #[serde(default)]
+    graph: GraphFormat,
 }
 
-impl Payload {
-    /// Gets requested bytes.
-    fn bytes(&self) -> &[u8] {
-        // With this method it's possible to add logic like this in future is needed
-        /*
-        let start = self.start.unwrap_or(0);
-        let stop = self.start.unwrap_or_else(|| self.data.len());
-        let range = start..stop;
-         */
-        &self.bytes
-    }
+/// Input to [FUNCTIONS_ENDPOINT].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FunctionsPayload {
+    #[serde(flatten)]
+    payload: Payload,
+    /// Known function entry addresses.
+    #[serde(default)]
+    entries: Vec<usize>,
 }
 
-pub async fn handle_mos6502(
-    Json(payload): Json<Payload>,
-) -> Result<Response, DisasmError<<mos6502::Mos6502 as Disassembler>::Error>> {
-    let disasm = mos6502::Mos6502;
-    let res = disasm.disassemble(payload.bytes(), &payload.format)?;
-
-    Ok(Json(res).into_response())
+/// Input to [DETECT_ENDPOINT].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DetectPayload {
+    /// Input binary code, possibly in a container format.
+    bytes: Vec<u8>,
 }
 
-pub async fn handle_risc_v(
-    Json(payload): Json<Payload>,
-) -> Result<Response, DisasmError<<risc_v::RiscV as Disassembler>::Error>> {
-    let disasm = &risc_v::RiscV::new(payload.arch.width.ok_or(DisasmError::MissingInfo)?)?;
-    let res = disasm.disassemble(payload.bytes(), &payload.format)?;
-
-    Ok(Json(res).into_response())
+/// Input to [DIFF_ENDPOINT], the options of [Payload] apply to both versions.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiffPayload {
+    /// The original version in [Payload::bytes].
+    #[serde(flatten)]
+    payload: Payload,
+    /// The patched version.
+    patched: Vec<u8>,
 }
 
-pub async fn handle_x86(
-    Json(payload): Json<Payload>,
-) -> Result<Response, DisasmError<<x86::X86 as Disassembler>::Error>> {
-    let syntax = match &payload.arch.syntax {
-        Some(requested) => x86::Syntax::from_str(requested)?,
-        None => x86::Syntax::default(),
-    };
-
-    let disasm = x86::X86::new(syntax, payload.arch.width.ok_or(DisasmError::MissingInfo)?)?;
-    let res = disasm.disassemble(payload.bytes(), &payload.format)?;
-
-    Ok(Json(res).into_response())
+/// Input to [SEARCH_ENDPOINT].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchPayload {
+    #[serde(flatten)]
+    payload: Payload,
+    /// See [Pattern].
+    pattern: String,
+    /// Instructions shown before and after each match.
+    #[serde(default)]
+    context: usize,
 }
 
-/// These are integration tests, however it's hard to move them to root/test
-/// due to cargo's inability to use `bin`'s symbols over there.
-///
-/// **These tests expect to find a running server on [tests::URL].**
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
-
-    use disassembler::*;
-    use reqwest::StatusCode;
+/// Format of a graph returned by [CFG_ENDPOINT].
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+pub enum GraphFormat {
+    /// A [Cfg](crate::Cfg) object.
+    #[default]
+    Json,
+    /// Graphviz DOT text.
+    Dot,
+}
 
-    use super::*;
+/// A disassembly with analysis results, returned instead of plain lines if any is requested.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Listing {
+    /// Disassembly lines.
+    pub lines: Vec<String>,
+    /// See [AssemblerOutput::xrefs].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub xrefs: Option<Vec<Xref>>,
+}
 
-    // I think that unit test should not depend on external system state when they can,
-    // that's why I prefer to start the server here once.
-    // For the sake of simplicity, I decided to leave the requirement to run the server first.
-    // static SERVER_STARTED: AtomicBool = AtomicBool::new(false);
+/// A disassembler description returned by [ARCHITECTURES_ENDPOINT].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Architecture {
+    #[serde(flatten)]
+    pub capabilities: Capabilities,
+    /// Disassembly endpoint path.
+    pub endpoint: String,
+}
 
-    const MOS6502_TEST_BYTES: &[u8] = &[0xa9, 0xbd, 0xa0, 0xbd, 0x20, 0x28, 0xba];
+impl Architecture {
+    /// Describes a registered disassembler, served by [DISASSEMBLE_ARCH_ENDPOINT].
+    fn new(capabilities: &Capabilities) -> Self {
+        Self {
+            endpoint: DISASSEMBLE_ARCH_ENDPOINT.replace(":arch", &capabilities.name),
+            capabilities: capabilities.clone(),
+        }
+    }
+}
 
-    fn url(endpoint: &str) -> String {
-        format!("http://localhost:9999{endpoint}")
+/// [Json] extractor that reports rejections as [ErrorBody].
+pub struct JsonPayload<T>(pub T);
+
+#[async_trait]
+impl<S: Send + Sync, T: DeserializeOwned> FromRequest<S> for JsonPayload<T> {
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => {
+                let body = ErrorBody {
+                    code: "invalid_payload".to_string(),
+                    message: rejection.body_text(),
+                    field: None,
+                    allowed: vec![],
+                };
+                Err((rejection.status(), Json(body)).into_response())
+            }
+        }
     }
+}
 
-    #[tokio::test]
-    async fn test_mos6502() {
-        let expected = [
-            "0000 A9 BD    LDA #$BD",
-            "0002 A0 BD    LDY #$BD",
-            "0004 20 28 BA JSR $BA28",
-        ];
+impl CfgPayload {
+    /// Constructs a new request for the function at `entry`.
+    pub fn new(payload: Payload, entry: usize, graph: GraphFormat) -> Self {
+        Self {
+            payload,
+            entry,
+            graph,
+        }
+    }
+}
 
-        assert_eq!(
-            expected.as_slice(),
-            test_mos6502_impl(MOS6502_TEST_BYTES).await
-        );
+impl FunctionsPayload {
+    /// Constructs a new request, functions at `entries` are added to the detected ones.
+    pub fn new(payload: Payload, entries: Vec<usize>) -> Self {
+        Self { payload, entries }
+    }
+}
 
-        let bytes = tokio::fs::read("test-bin/mos6502/test1.bin").await.unwrap();
+impl DetectPayload {
+    /// Constructs a new request.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+}
 
-        let expected = tokio::fs::read_to_string("test-bin/mos6502/test1.out")
-            .await
-            .unwrap();
-        let expected = expected.lines().collect::<Vec<_>>();
+impl DiffPayload {
+    /// Constructs a new request comparing the bytes of `payload` to `patched`.
+    pub fn new(payload: Payload, patched: Vec<u8>) -> Self {
+        Self { payload, patched }
+    }
+}
 
-        assert_eq!(expected, test_mos6502_impl(&bytes).await);
+impl SearchPayload {
+    /// Constructs a new request without context.
+    pub fn new(payload: Payload, pattern: &str) -> Self {
+        Self {
+            payload,
+            pattern: pattern.to_string(),
+            context: 0,
+        }
+    }
 
-        let bytes = tokio::fs::read("test-bin/mos6502/test2.bin").await.unwrap();
+    /// Sets the number of instructions shown before and after each match.
+    pub fn with_context(mut self, context: usize) -> Self {
+        self.context = context;
+        self
+    }
+}
 
-        let expected = tokio::fs::read_to_string("test-bin/mos6502/test2.out")
-            .await
-            .unwrap();
-        let expected = expected.lines().collect::<Vec<_>>();
+impl Payload {
+    /// Constructs a new request.
+    pub fn new(bytes: Vec<u8>, arch: ArchOptions, format: AssemblerOutput) -> Self {
+        Self {
+            bytes,
+            arch,
+            format,
+            input: Input::Raw,
+            symbols: None,
+        }
+    }
 
-        assert_eq!(expected.as_slice(), test_mos6502_impl(&bytes).await);
+    /// Sets the container format of the bytes.
+    pub fn with_input(mut self, input: Input) -> Self {
+        self.input = input;
+        self
     }
 
-    async fn test_mos6502_impl(bytes: &[u8]) -> Vec<String> {
-        let client = reqwest::Client::builder().build().unwrap();
+    /// Attaches a tool-native symbol file.
+    pub fn with_symbols(mut self, symbols: SymbolFile) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
 
-        let payload = Payload {
-            bytes: bytes.to_vec(),
-            format: AssemblerOutput::default(),
-            arch: ArchOptions {
-                width: Some(BitWidth::Bit8),
-                syntax: None,
-            },
-        };
+    /// Formatting options with the symbol file applied.
+    fn format(&self) -> Result<AssemblerOutput, SymbolError> {
+        match &self.symbols {
+            Some(file) => self.format.clone().with_symbol_file(file),
+            None => Ok(self.format.clone()),
+        }
+    }
 
-        let url = url(MOS6502_ENDPOINT);
-        let resp = client.post(url).json(&payload).send().await.unwrap();
-        resp.json().await.unwrap()
+    /// Loads the input and constructs its disassembler, `arch` takes precedence over
+    /// the requested and the detected [ArchOptions::arch].
+    fn load(
+        &self,
+        registry: &DisassemblerRegistry,
+        arch: Option<&str>,
+    ) -> Result<Loaded<'_>, DisasmError<DynError>> {
+        self.load_bytes(registry, arch, self.bytes())
     }
 
-    #[tokio::test]
-    async fn test_mos6502_offset() {
-        let client = reqwest::Client::new();
-        let payload = Payload {
-            bytes: MOS6502_TEST_BYTES.to_vec(),
-            format: AssemblerOutput::default().with_addresses(ShowAddress::Start(0xA)),
-            arch: ArchOptions {
-                width: Some(BitWidth::Bit8),
-                syntax: None,
-            },
+    /// Loads other `bytes` with the options of this request, see [Payload::load].
+    fn load_bytes<'a>(
+        &self,
+        registry: &DisassemblerRegistry,
+        arch: Option<&str>,
+        bytes: &'a [u8],
+    ) -> Result<Loaded<'a>, DisasmError<DynError>> {
+        let image = self.input.load(bytes).map_err(DisasmError::Load)?;
+        let options = match &image {
+            Some(image) => self.arch.clone().or(&image.arch),
+            None => self.arch.clone(),
         };
+        let arch = arch
+            .or(options.arch())
+            .ok_or(DisasmError::MissingInfo("arch.arch"))?;
 
-        let url = url(MOS6502_ENDPOINT);
-        let resp = client.post(url).json(&payload).send().await.unwrap();
-        let resp: Vec<String> = resp.json().await.unwrap();
-
-        let expected = [
-            "000A A9 BD    LDA #$BD",
-            "000C A0 BD    LDY #$BD",
-            "000E 20 28 BA JSR $BA28",
-        ];
-        assert_eq!(expected.as_slice(), resp);
+        Ok(Loaded {
+            bytes,
+            disasm: registry.get(arch, &options)?,
+            format: self.format().map_err(DisasmError::Symbols)?,
+            image,
+        })
     }
 
-    #[tokio::test]
-    async fn test_mos6502_byte_offset() {
-        let client = reqwest::Client::new();
-        let payload = Payload {
-            bytes: MOS6502_TEST_BYTES.to_vec(),
-            format: AssemblerOutput::default().with_addresses(ShowAddress::Start(0xA)),
-            arch: ArchOptions {
-                width: Some(BitWidth::Bit8),
-                syntax: None,
-            },
-        };
+    /// Gets requested bytes.
+    fn bytes(&self) -> &[u8] {
+        // With this method it's possible to add logic like this in future is needed
+        /*
+        let start = self.start.unwrap_or(0);
+        let stop = self.start.unwrap_or_else(|| self.data.len());
+        let range = start..stop;
+         */
+        &self.bytes
+    }
+}
 
-        let url = url(MOS6502_ENDPOINT);
-        let resp = client.post(url).json(&payload).send().await.unwrap();
-        let resp: Vec<String> = resp.json().await.unwrap();
+/// A request ready to be disassembled.
+struct Loaded<'a> {
+    bytes: &'a [u8],
+    /// [None] for a raw input.
+    image: Option<Image>,
+    disasm: Box<dyn DynDisassembler>,
+    format: AssemblerOutput,
+}
 
-        let expected = [
-            "000A A9 BD    LDA #$BD",
-            "000C A0 BD    LDY #$BD",
-            "000E 20 28 BA JSR $BA28",
-        ];
-        assert_eq!(expected.as_slice(), resp);
+impl Loaded<'_> {
+    fn disassemble(&self) -> Result<Vec<String>, DisasmError<DynError>> {
+        match &self.image {
+            Some(image) => image.disassemble(&*self.disasm, &self.format),
+            None => self.disasm.disassemble_dyn(self.bytes, &self.format),
+        }
     }
 
-    #[tokio::test]
-    async fn test_mos6502_no_address() {
-        let client = reqwest::Client::new();
-        let payload = Payload {
-            bytes: MOS6502_TEST_BYTES.to_vec(),
-            format: AssemblerOutput::default().with_addresses(ShowAddress::None),
-            arch: ArchOptions {
-                width: Some(BitWidth::Bit8),
-                syntax: None,
-            },
-        };
+    fn decode(&self) -> Result<Vec<DecodedInstruction>, DisasmError<DynError>> {
+        match &self.image {
+            Some(image) => image.decode(&*self.disasm, &self.format),
+            None => self.disasm.decode_dyn(self.bytes, &self.format),
+        }
+    }
+}
 
-        let url = url(MOS6502_ENDPOINT);
-        let resp = client.post(url).json(&payload).send().await.unwrap();
-        let resp: Vec<String> = resp.json().await.unwrap();
+/// Handles [ARCHITECTURES_ENDPOINT], describes every disassembler of the registry.
+pub async fn handle_architectures(
+    State(registry): State<Arc<DisassemblerRegistry>>,
+) -> Json<Vec<Architecture>> {
+    Json(registry.capabilities().map(Architecture::new).collect())
+}
 
-        let expected = ["LDA #$BD", "LDY #$BD", "JSR $BA28"];
-        assert_eq!(expected.as_slice(), resp);
+/// Handles an architecture-specific endpoint, only a raw input is accepted.
+pub async fn handle<D: Disassembler>(
+    JsonPayload(payload): JsonPayload<Payload>,
+) -> Result<Response, DisasmError<D::Error>> {
+    if payload.input != Input::Raw {
+        return Err(DisasmError::UnsupportedOption("input"));
     }
+    let disasm = D::from_options(&payload.arch)?;
+    let format = payload.format().map_err(DisasmError::Symbols)?;
+    let lines = disasm.disassemble(payload.bytes(), &format)?;
 
-    #[tokio::test]
-    async fn test_mos6502_no_address_lowercase() {
-        let client = reqwest::Client::new();
-        let payload = Payload {
-            bytes: MOS6502_TEST_BYTES.to_vec(),
-            format: AssemblerOutput::default()
-                .with_addresses(ShowAddress::None)
-                .with_upper_case(false),
-            arch: ArchOptions {
-                width: Some(BitWidth::Bit8),
-                syntax: None,
-            },
-        };
+    respond(lines, &format, || disasm.decode(payload.bytes(), &format))
+}
 
-        let url = url(MOS6502_ENDPOINT);
-        let resp = client.post(url).json(&payload).send().await.unwrap();
-        let resp: Vec<String> = resp.json().await.unwrap();
+/// Handles [DISASSEMBLE_ENDPOINT].
+pub async fn handle_disassemble(
+    State(registry): State<Arc<DisassemblerRegistry>>,
+    JsonPayload(payload): JsonPayload<Payload>,
+) -> Result<Response, DisasmError<DynError>> {
+    disassemble_with(&registry, None, &payload)
+}
 
-        let expected = ["lda #$bd", "ldy #$bd", "jsr $ba28"];
-        assert_eq!(expected.as_slice(), resp);
-    }
+/// Handles [DISASSEMBLE_ARCH_ENDPOINT], [ArchOptions::arch] is ignored.
+pub async fn handle_disassemble_arch(
+    State(registry): State<Arc<DisassemblerRegistry>>,
+    Path(arch): Path<String>,
+    JsonPayload(payload): JsonPayload<Payload>,
+) -> Result<Response, DisasmError<DynError>> {
+    disassemble_with(&registry, Some(&arch), &payload)
+}
 
-    #[tokio::test]
-    async fn test_mos6502_unsupported() {
-        let client = reqwest::Client::new();
-        let payload = Payload {
-            bytes: MOS6502_TEST_BYTES.to_vec(),
-            format: AssemblerOutput::default()
-                .with_cycles(true)
-                .with_symbol_table(HashMap::from([(
-                    SymbolInfo::new(0xBA28, Scope::Global),
-                    "SUBROUTINE".to_string(),
-                )])),
-            arch: ArchOptions {
-                width: Some(BitWidth::Bit8),
-                syntax: None,
-            },
-        };
+/// Handles [CFG_ENDPOINT].
+pub async fn handle_cfg(
+    State(registry): State<Arc<DisassemblerRegistry>>,
+    JsonPayload(CfgPayload {
+        payload,
+        entry,
+        graph,
+    }): JsonPayload<CfgPayload>,
+) -> Result<Response, DisasmError<DynError>> {
+    let cfg = cfg(&payload.load(&registry, None)?.decode()?, entry);
+
+    Ok(match graph {
+        GraphFormat::Json => Json(cfg).into_response(),
+        GraphFormat::Dot => {
+            ([(header::CONTENT_TYPE, "text/vnd.graphviz")], cfg.to_dot()).into_response()
+        }
+    })
+}
 
-        let url = url(MOS6502_ENDPOINT);
-        let resp = client.post(url).json(&payload).send().await.unwrap();
+/// Handles [FUNCTIONS_ENDPOINT].
+pub async fn handle_functions(
+    State(registry): State<Arc<DisassemblerRegistry>>,
+    JsonPayload(FunctionsPayload {
+        payload,
+        mut entries,
+    }): JsonPayload<FunctionsPayload>,
+) -> Result<Json<CallGraph>, DisasmError<DynError>> {
+    let loaded = payload.load(&registry, None)?;
+    let instructions = loaded.decode()?;
+    entries.extend(loaded.disasm.prologues_dyn(&instructions));
+
+    Ok(Json(call_graph(&instructions, &entries)))
+}
 
-        assert_eq!(
-            resp.error_for_status_ref().unwrap_err().status().unwrap(),
-            StatusCode::NOT_IMPLEMENTED
-        );
+/// Handles [DETECT_ENDPOINT].
+pub async fn handle_detect(
+    State(registry): State<Arc<DisassemblerRegistry>>,
+    JsonPayload(payload): JsonPayload<DetectPayload>,
+) -> Json<Vec<Candidate>> {
+    Json(registry.detect(&payload.bytes))
+}
 
-        let error: String = resp.json().await.unwrap();
-        assert_eq!("Unsupported disassembler option", error);
-    }
+/// Handles [DIFF_ENDPOINT], returns the lines of a unified diff.
+pub async fn handle_diff(
+    State(registry): State<Arc<DisassemblerRegistry>>,
+    JsonPayload(DiffPayload { payload, patched }): JsonPayload<DiffPayload>,
+) -> Result<Json<Vec<String>>, DisasmError<DynError>> {
+    let old = payload.load(&registry, None)?.decode()?;
+    let new = payload.load_bytes(&registry, None, &patched)?.decode()?;
+    let diff = diff(&old, &new);
+
+    Ok(Json(
+        diff.to_unified().lines().map(str::to_string).collect(),
+    ))
+}
 
-    #[tokio::test]
-    async fn test_mos6502_with_x86() {
-        let client = reqwest::Client::new();
-        let bytes = std::fs::read("test-bin/x86/test.bin").unwrap();
+/// Handles [SEARCH_ENDPOINT].
+pub async fn handle_search(
+    State(registry): State<Arc<DisassemblerRegistry>>,
+    JsonPayload(SearchPayload {
+        payload,
+        pattern,
+        context,
+    }): JsonPayload<SearchPayload>,
+) -> Result<Json<Vec<Match>>, DisasmError<DynError>> {
+    let pattern: Pattern = pattern.parse().map_err(DisasmError::Pattern)?;
+    let instructions = payload.load(&registry, None)?.decode()?;
+
+    Ok(Json(search(&instructions, &pattern, context)))
+}
 
-        let payload = Payload {
-            bytes,
-            format: AssemblerOutput::default().with_stop(0xA),
-            arch: ArchOptions {
-                width: Some(BitWidth::Bit8),
-                syntax: None,
-            },
-        };
+/// Handles requests to unknown endpoints.
+pub async fn handle_not_found() -> Response {
+    let body = ErrorBody {
+        code: "not_found".to_string(),
+        message: "Unknown endpoint".to_string(),
+        field: None,
+        allowed: vec![],
+    };
 
-        let url = url(MOS6502_ENDPOINT);
-        let resp = client.post(url).json(&payload).send().await.unwrap();
-        let resp: Vec<String> = resp.json().await.unwrap();
-
-        let expected = [
-            "0000 7F",
-            "0001 45 4C    EOR $4C",
-            "0003 46 02    LSR $02",
-            "0005 01 01    ORA ($01,X)",
-            "0007 00       BRK",
-            "0008 00       BRK",
-            "0009 00       BRK",
-            "000A 00       BRK",
-        ];
-        assert_eq!(expected.as_slice(), resp)
-    }
+    (StatusCode::NOT_FOUND, Json(body)).into_response()
+}
 
-    #[tokio::test]
-    async fn test_x86() {
-        let client = reqwest::Client::new();
-        let bytes = tokio::fs::read("test-bin/x86/test.bin").await.unwrap();
+fn disassemble_with(
+    registry: &DisassemblerRegistry,
+    arch: Option<&str>,
+    payload: &Payload,
+) -> Result<Response, DisasmError<DynError>> {
+    let loaded = payload.load(registry, arch)?;
+    let lines = loaded.disassemble()?;
 
-        let mut payload = Payload {
-            bytes,
-            format: AssemblerOutput::default().with_stop(0xA),
-            arch: ArchOptions {
-                width: Some(BitWidth::Bit64),
-                syntax: Some("att".to_string()),
-            },
-        };
+    respond(lines, &loaded.format, || loaded.decode())
+}
 
-        let url = url(X86_ENDPOINT);
-        let resp = client.post(&url).json(&payload).send().await.unwrap();
-        let resp: Vec<String> = resp.json().await.unwrap();
-
-        let expected = [
-            "0x00000000 JG 0x0000000000000047",
-            "0x00000002 ADD (%RCX),%R8B",
-            "0x00000006 ADD %EAX,(%RAX)",
-            "0x00000008 ADD %AL,(%RAX)",
-        ];
-        assert_eq!(expected.as_slice(), resp);
-
-        payload.arch.syntax = None;
-
-        let resp = client.post(url).json(&payload).send().await.unwrap();
-        let resp: Vec<String> = resp.json().await.unwrap();
-
-        let expected = [
-            "0x00000000 JG SHORT 0000000000000047h",
-            "0x00000002 ADD R8B,[RCX]",
-            "0x00000006 ADD [RAX],EAX",
-            "0x00000008 ADD [RAX],AL",
-        ];
-        assert_eq!(expected.as_slice(), resp)
+/// Responds with plain `lines`, or with a [Listing] if `format` requests analysis results.
+fn respond<E: StdError>(
+    lines: Vec<String>,
+    format: &AssemblerOutput,
+    decode: impl FnOnce() -> Result<Vec<DecodedInstruction>, DisasmError<E>>,
+) -> Result<Response, DisasmError<E>> {
+    if !format.xrefs() {
+        return Ok(Json(lines).into_response());
     }
 
-    #[tokio::test]
-    async fn test_unimplemented() {
-        let client = reqwest::Client::new();
-        let bytes = tokio::fs::read("test-bin/x86/test.bin").await.unwrap();
-
-        let payload = Payload {
-            bytes,
-            format: AssemblerOutput::default(),
-            arch: ArchOptions {
-                width: Some(BitWidth::Bit16),
-                syntax: None,
-            },
-        };
+    let listing = Listing {
+        lines,
+        xrefs: Some(xrefs(&decode()?)),
+    };
+    Ok(Json(listing).into_response())
+}
 
-        let url = url(RISC_V_ENDPOINT);
-        let resp = client.post(&url).json(&payload).send().await.unwrap();
+impl From<ErrorKind> for StatusCode {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::InvalidRequest => StatusCode::BAD_REQUEST,
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::Unsupported => StatusCode::NOT_IMPLEMENTED,
+            ErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
-        assert_eq!(
-            resp.error_for_status_ref().unwrap_err().status().unwrap(),
-            StatusCode::NOT_IMPLEMENTED
-        );
+impl<E: StdError + ErrorDetails> IntoResponse for DisasmError<E> {
+    fn into_response(self) -> Response {
+        let code = StatusCode::from(self.kind());
 
-        let error: String = resp.json().await.unwrap();
-        assert_eq!("The implementation has not been done", error);
+        (code, Json(ErrorBody::new(&self))).into_response()
     }
 }
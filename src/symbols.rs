@@ -0,0 +1,388 @@
+//! Import of symbol files produced by assemblers and emulators, see [AssemblerOutput::symbol_table].
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{AssemblerOutput, ErrorDetails, ErrorKind, Scope, SymbolInfo};
+
+/// A tool-native symbol file format.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+pub enum SymbolFormat {
+    /// VICE monitor labels, `al C:c000 .start`, also written by `ld65 -Ln`.
+    Vice,
+    /// `ld65 --dbgfile` debug information.
+    Ld65Dbg,
+    /// `ld65 -m` map file, only the exports are used.
+    Ld65Map,
+    /// FCEUX `.nl` name list, `$C000#Reset#comment`.
+    Fceux,
+    /// Mesen `.mlb` labels, `P:0000:Reset:comment`.
+    Mesen,
+    /// Plain `NAME = $ADDR` assignments.
+    Assignments,
+}
+
+/// A symbol file sent along with a disassembly request.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct SymbolFile {
+    /// Detected from the text if omitted.
+    #[serde(default)]
+    pub format: Option<SymbolFormat>,
+    pub text: String,
+}
+
+/// A symbol file parsing error.
+#[derive(Error, Debug, PartialEq, Eq, Clone)]
+pub enum SymbolError {
+    #[error("Unknown symbol file format")]
+    UnknownFormat,
+    /// Contains the 1-based line number and the reason.
+    #[error("Invalid symbol file line {0}: {1}")]
+    InvalidLine(usize, String),
+}
+
+impl ErrorDetails for SymbolError {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::InvalidRequest
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownFormat => "unknown_symbol_format",
+            Self::InvalidLine(..) => "invalid_symbol_file",
+        }
+    }
+
+    fn field(&self) -> Option<&'static str> {
+        Some("symbols")
+    }
+
+    fn allowed(&self) -> Vec<String> {
+        match self {
+            Self::UnknownFormat => SymbolFormat::NAMES.map(str::to_string).to_vec(),
+            Self::InvalidLine(..) => vec![],
+        }
+    }
+}
+
+/// Memory type prefixes of [SymbolFormat::Mesen] and the CPU address of their first byte.
+/// PRG ROM offsets are mapped as unbanked 32K ROM.
+const MESEN_MEMORY: [(&str, usize); 10] = [
+    ("R", 0),
+    ("NesInternalRam", 0),
+    ("G", 0),
+    ("NesMemory", 0),
+    ("W", 0x6000),
+    ("NesWorkRam", 0x6000),
+    ("S", 0x6000),
+    ("NesSaveRam", 0x6000),
+    ("P", 0x8000),
+    ("NesPrgRom", 0x8000),
+];
+
+impl SymbolFormat {
+    /// Names as they are serialized.
+    pub const NAMES: [&'static str; 6] = [
+        "Vice",
+        "Ld65Dbg",
+        "Ld65Map",
+        "Fceux",
+        "Mesen",
+        "Assignments",
+    ];
+
+    /// Guesses the format of `text` by its first meaningful line.
+    pub fn detect(text: &str) -> Option<Self> {
+        if text
+            .lines()
+            .any(|line| line.trim() == "Exports list by name:")
+        {
+            return Some(Self::Ld65Map);
+        }
+
+        let line = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with(';'))?;
+        if line.starts_with("version") && line.contains("major=") {
+            Some(Self::Ld65Dbg)
+        } else if line.starts_with("al ") {
+            Some(Self::Vice)
+        } else if line.starts_with('$') && line.contains('#') {
+            Some(Self::Fceux)
+        } else if mesen(line).is_some() {
+            Some(Self::Mesen)
+        } else if line.contains('=') {
+            Some(Self::Assignments)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses a symbol file, its format is detected if `format` is [None].
+/// Names starting with `@` and ones in nested ld65 scopes are [Scope::Local].
+/// The first name of an address is kept.
+pub fn parse_symbols(
+    text: &str,
+    format: Option<SymbolFormat>,
+) -> Result<HashMap<SymbolInfo, String>, SymbolError> {
+    let format = format
+        .or_else(|| SymbolFormat::detect(text))
+        .ok_or(SymbolError::UnknownFormat)?;
+
+    let mut table = HashMap::new();
+    let mut exports = false;
+    for (index, line) in text.lines().enumerate() {
+        let invalid = |reason: &str| SymbolError::InvalidLine(index + 1, reason.to_string());
+        let line = line.trim();
+        if line.is_empty() {
+            exports = false;
+            continue;
+        }
+
+        let symbols = match format {
+            SymbolFormat::Vice => {
+                vice(line).ok_or_else(|| invalid("expected `al ADDRESS .NAME`"))?
+            }
+            SymbolFormat::Ld65Dbg => dbg(line).ok_or_else(|| invalid("invalid `sym` line"))?,
+            SymbolFormat::Ld65Map => {
+                if line == "Exports list by name:" {
+                    exports = true;
+                    continue;
+                }
+                if !exports || line.starts_with('-') {
+                    continue;
+                }
+                map(line).ok_or_else(|| invalid("expected `NAME VALUE FLAGS` columns"))?
+            }
+            SymbolFormat::Fceux => {
+                fceux(line).ok_or_else(|| invalid("expected `$ADDRESS#NAME#COMMENT`"))?
+            }
+            SymbolFormat::Mesen => {
+                mesen(line).ok_or_else(|| invalid("expected `TYPE:ADDRESS:NAME`"))?
+            }
+            SymbolFormat::Assignments => {
+                assignment(line).ok_or_else(|| invalid("expected `NAME = ADDRESS`"))?
+            }
+        };
+
+        for (symbol, name) in symbols {
+            table.entry(symbol).or_insert(name);
+        }
+    }
+
+    Ok(table)
+}
+
+/// Symbols of a line, an empty list if the line has none.
+type Symbols = Vec<(SymbolInfo, String)>;
+
+/// A symbol, local if it is a ca65 cheap local label.
+fn symbol(address: usize, name: &str) -> (SymbolInfo, String) {
+    let scope = match name.starts_with('@') {
+        true => Scope::Local,
+        false => Scope::Global,
+    };
+    (SymbolInfo::new(address, scope), name.to_string())
+}
+
+fn vice(line: &str) -> Option<Symbols> {
+    let mut words = line.split_whitespace();
+    if words.next()? != "al" {
+        return None;
+    }
+    let address = words.next()?;
+    // the memory space prefix, e.g. `C:`
+    let address = address
+        .split_once(':')
+        .map_or(address, |(_, address)| address);
+    let name = words.next()?.strip_prefix('.')?;
+
+    Some(vec![symbol(hex(address)?, name)])
+}
+
+fn dbg(line: &str) -> Option<Symbols> {
+    let Some(attributes) = line.strip_prefix("sym") else {
+        return Some(vec![]);
+    };
+    let attributes: HashMap<_, _> = attributes
+        .trim()
+        .split(',')
+        .filter_map(|attribute| attribute.split_once('='))
+        .collect();
+
+    // imports and constants are not labels
+    if attributes.get("type") != Some(&"lab") {
+        return Some(vec![]);
+    }
+    let name = attributes.get("name")?.trim_matches('"');
+    let value = attributes.get("val")?.strip_prefix("0x")?;
+    // the top level scope has id 0, cheap locals have a parent instead of a scope
+    let scope = match attributes.get("scope") {
+        Some(&"0") if !name.starts_with('@') => Scope::Global,
+        _ => Scope::Local,
+    };
+
+    Some(vec![(
+        SymbolInfo::new(hex(value)?, scope),
+        name.to_string(),
+    )])
+}
+
+fn map(line: &str) -> Option<Symbols> {
+    let words: Vec<_> = line.split_whitespace().collect();
+    if words.len() % 3 != 0 {
+        return None;
+    }
+
+    words
+        .chunks(3)
+        .map(|columns| Some(symbol(hex(columns[1])?, columns[0])))
+        .collect()
+}
+
+fn fceux(line: &str) -> Option<Symbols> {
+    let mut fields = line.split('#');
+    let address = fields.next()?.strip_prefix('$')?;
+    // an array, e.g. `$0300/10`
+    let address = address
+        .split_once('/')
+        .map_or(address, |(address, _)| address);
+    let name = fields.next()?.trim();
+
+    let address = hex(address)?;
+    Some(match name {
+        "" => vec![],
+        name => vec![symbol(address, name)],
+    })
+}
+
+fn mesen(line: &str) -> Option<Symbols> {
+    let mut fields = line.splitn(4, ':');
+    let memory = fields.next()?;
+    let (_, base) = MESEN_MEMORY.iter().find(|(prefix, _)| *prefix == memory)?;
+    let address = fields.next()?;
+    // a range, e.g. `0010-0011`
+    let address = hex(address.split_once('-').map_or(address, |(start, _)| start))?;
+    let name = fields.next().unwrap_or_default().trim();
+
+    // PRG ROM offsets outside of the CPU address space depend on the mapper
+    let address = base
+        .checked_add(address)
+        .filter(|address| *address <= 0xFFFF);
+    match address {
+        Some(address) if !name.is_empty() => Some(vec![symbol(address, name)]),
+        _ => Some(vec![]),
+    }
+}
+
+fn assignment(line: &str) -> Option<Symbols> {
+    let line = line.split_once(';').map_or(line, |(line, _)| line).trim();
+    if line.is_empty() {
+        return Some(vec![]);
+    }
+    let (name, value) = line.split_once('=')?;
+    // `:=` defines a label in ca65
+    let name = name.trim().trim_end_matches(':').trim_end();
+    let value = value.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+
+    let address = match value.strip_prefix('$').or_else(|| value.strip_prefix("0x")) {
+        Some(value) => hex(value)?,
+        None => value.parse().ok()?,
+    };
+    Some(vec![symbol(address, name)])
+}
+
+fn hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+impl AssemblerOutput {
+    /// Adds symbols of a tool-native `file` to the symbol table.
+    pub fn with_symbol_file(self, file: &SymbolFile) -> Result<Self, SymbolError> {
+        let mut table = self.symbol_table().cloned().unwrap_or_default();
+        for (symbol, name) in parse_symbols(&file.text, file.format)? {
+            table.entry(symbol).or_insert(name);
+        }
+
+        Ok(self.with_symbol_table(table))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<(usize, Scope, String)> {
+        let mut symbols: Vec<_> = parse_symbols(text, None)
+            .unwrap()
+            .into_iter()
+            .map(|(symbol, name)| (symbol.address(), symbol.scope(), name))
+            .collect();
+        symbols.sort();
+        symbols
+    }
+
+    #[test]
+    fn test_formats() {
+        let expected = vec![
+            (0xC000, Scope::Global, "start".to_string()),
+            (0xC010, Scope::Local, "@loop".to_string()),
+        ];
+
+        assert_eq!(parse("al C:c000 .start\nal 00C010 .@loop\n"), expected);
+        assert_eq!(
+            parse(
+                "version\tmajor=2,minor=0\n\
+                 sym\tid=0,name=\"start\",addrsize=absolute,scope=0,def=1,val=0xC000,type=lab\n\
+                 sym\tid=1,name=\"@loop\",addrsize=absolute,scope=1,def=2,val=0xC010,type=lab\n\
+                 sym\tid=2,name=\"SIZE\",addrsize=zeropage,scope=0,def=3,val=0x10,type=equ\n"
+            ),
+            expected
+        );
+        assert_eq!(
+            parse(
+                "Modules list:\n-------------\n\n\
+                 Exports list by name:\n---------------------\n\
+                 start                     00C000 RLA    @loop                     00C010 RLA\n\n\
+                 Exports list by value:\n"
+            ),
+            expected
+        );
+        assert_eq!(
+            parse("$C000#start#entry\n$C010#@loop#\n$0300/10##\n"),
+            expected
+        );
+        assert_eq!(
+            parse("P:4000:start:entry\nNesPrgRom:4010:@loop\nR:0010-0011::pointer\n"),
+            expected
+        );
+        // out of the CPU address space, also when the offset overflows and in the detected line
+        assert_eq!(
+            parse("P:FFFFFFFFFFFFFFFF:boom\nP:4000:start\nP:C010:far\nP:4010:@loop\n"),
+            expected
+        );
+        assert_eq!(parse("start = $C000 ; entry\n@loop := 49168\n"), expected);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse_symbols("nothing", None),
+            Err(SymbolError::UnknownFormat)
+        );
+        assert_eq!(
+            parse_symbols("a = $10\nb = c\n", None),
+            Err(SymbolError::InvalidLine(
+                2,
+                "expected `NAME = ADDRESS`".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Golden-file regression corpus.
+//!
+//! Every `test-bin/<arch>/<name>.bin` is disassembled by the `<arch>` disassembler
+//! in every mode of its optional `<name>.json` sidecar, a map of a mode name to [Mode].
+//! A mode may name an executable [Input] format, its sections are disassembled then.
+//! Modes of formats disabled by features are skipped.
+//!
+//! Every output mode of the disassembler is derived from the `default` mode as well,
+//! see [derived_modes], a sidecar mode of the same name takes precedence.
+//! The output of the `default` mode is compared to `<name>.out`, others to `<name>.<mode>.out`.
+//!
+//! Run with `BLESS=1` to regenerate the expected outputs, a missing one fails the test otherwise.
+
+// the corpus has cases of these architectures only
+#![cfg(any(feature = "mos6502", feature = "x86"))]
+
+use std::{collections::BTreeMap, env, fs, path::Path};
+
+use serde::Deserialize;
+
+use disassembler::{
+    ArchOptions, AssemblerOutput, Capabilities, DisassemblerRegistry, ErrorDetails, Input,
+    ShowAddress,
+};
+
+const CORPUS: &str = "test-bin";
+const DEFAULT_MODE: &str = "default";
+
+/// Disassembly options of a corpus case.
+#[derive(Debug, Default, Deserialize, Clone)]
+#[serde(default)]
+struct Mode {
+    arch: ArchOptions,
+    format: AssemblerOutput,
+    /// [Input] as it is serialized, e.g. `"Elf"`.
+    input: Option<serde_json::Value>,
+}
+
+#[test]
+fn test_golden() {
+    let bless = env::var_os("BLESS").is_some();
+    let registry = DisassemblerRegistry::builtin();
+    let mut failures = vec![];
+    let mut cases = 0;
+
+    for arch in fs::read_dir(CORPUS).unwrap() {
+        let arch = arch.unwrap().path();
+        let Some(arch_name) = arch.is_dir().then(|| file_name(&arch)) else {
+            continue;
+        };
+        // the architecture is disabled by features
+        let Some(caps) = registry.capabilities().find(|caps| caps.name == arch_name) else {
+            continue;
+        };
+
+        for bin in fs::read_dir(&arch).unwrap() {
+            let bin = bin.unwrap().path();
+            if bin.extension().is_none_or(|ext| ext != "bin") {
+                continue;
+            }
+
+            let bytes = fs::read(&bin).unwrap();
+            for (mode_name, mode) in modes(&bin, caps) {
+                let input = mode.input.unwrap_or_else(|| "Raw".into());
+                let Ok(input) = serde_json::from_value::<Input>(input) else {
+                    continue;
+                };
+                let image = input.load(&bytes).unwrap();
+                let arch = match &image {
+                    Some(image) => mode.arch.clone().or(&image.arch),
+                    None => mode.arch.clone(),
+                };
+                let actual = match registry
+                    .get(&arch_name, &arch)
+                    .and_then(|disasm| match &image {
+                        Some(image) => image.disassemble(&*disasm, &mode.format),
+                        None => disasm.disassemble_dyn(&bytes, &mode.format),
+                    }) {
+                    Ok(lines) => lines.join("\n"),
+                    Err(e) => format!("error[{}]: {e}", e.code()),
+                };
+
+                let expected_path = match mode_name.as_str() {
+                    DEFAULT_MODE => bin.with_extension("out"),
+                    mode_name => bin.with_extension(format!("{mode_name}.out")),
+                };
+
+                cases += 1;
+                if bless {
+                    fs::write(&expected_path, &actual).unwrap();
+                    continue;
+                }
+
+                let Ok(expected) = fs::read_to_string(&expected_path) else {
+                    failures.push(format!("{}: missing", expected_path.display()));
+                    continue;
+                };
+                if let Some(mismatch) = first_mismatch(&expected, &actual) {
+                    failures.push(format!("{}: {mismatch}", expected_path.display()));
+                }
+            }
+        }
+    }
+
+    assert!(cases > 0, "the corpus is empty");
+    assert!(
+        failures.is_empty(),
+        "{} of {cases} golden cases failed, run with `BLESS=1` to accept the changes:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
+
+/// Reads modes of a corpus case from its sidecar and adds the [derived_modes].
+fn modes(bin: &Path, caps: &Capabilities) -> BTreeMap<String, Mode> {
+    let mut modes: BTreeMap<String, Mode> = match fs::read_to_string(bin.with_extension("json")) {
+        Ok(sidecar) => serde_json::from_str(&sidecar)
+            .unwrap_or_else(|e| panic!("{}: invalid sidecar: {e}", bin.display())),
+        Err(_) => BTreeMap::new(),
+    };
+
+    let default = modes.entry(DEFAULT_MODE.to_string()).or_default().clone();
+    for (name, mode) in derived_modes(&default, caps) {
+        modes.entry(name).or_insert(mode);
+    }
+
+    modes
+}
+
+/// Output modes of a disassembler applied to the `default` mode:
+/// - `lower`, code only, without addresses and in the lower case
+/// - one per additional syntax, named after it
+/// - `cycles` if the cycle counts are supported
+fn derived_modes(default: &Mode, caps: &Capabilities) -> Vec<(String, Mode)> {
+    let mut modes = vec![(
+        "lower".to_string(),
+        Mode {
+            format: default
+                .format
+                .clone()
+                .with_addresses(ShowAddress::None)
+                .with_upper_case(false),
+            ..default.clone()
+        },
+    )];
+
+    // the first syntax is the default one
+    for syntax in caps.syntaxes.iter().skip(1) {
+        let mode = Mode {
+            arch: default.arch.clone().with_syntax(&syntax.name),
+            ..default.clone()
+        };
+        modes.push((syntax.name.clone(), mode));
+    }
+
+    if caps.output.cycles {
+        let mode = Mode {
+            format: default.format.clone().with_cycles(true),
+            ..default.clone()
+        };
+        modes.push(("cycles".to_string(), mode));
+    }
+
+    modes
+}
+
+/// Describes the first differing line.
+fn first_mismatch(expected: &str, actual: &str) -> Option<String> {
+    let mut expected = expected.lines();
+    let mut actual = actual.lines();
+
+    for line in 1.. {
+        match (expected.next(), actual.next()) {
+            (None, None) => return None,
+            (e, a) if e == a => continue,
+            (e, a) => {
+                return Some(format!(
+                    "line {line}: expected {:?}, got {:?}",
+                    e.unwrap_or("<EOF>"),
+                    a.unwrap_or("<EOF>")
+                ))
+            }
+        }
+    }
+
+    unreachable!()
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().into_owned()
+}
@@ -0,0 +1,885 @@
+//! End-to-end tests of the service, the router is driven in-process.
+
+#![cfg(all(
+    feature = "server",
+    feature = "mos6502",
+    feature = "x86",
+    feature = "riscv"
+))]
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use tower::ServiceExt;
+
+use disassembler::{server::*, *};
+
+const MOS6502_TEST_BYTES: &[u8] = &[0xa9, 0xbd, 0xa0, 0xbd, 0x20, 0x28, 0xba];
+
+/// Sends `request` to a fresh service instance.
+async fn send<T: DeserializeOwned>(request: Request<Body>) -> (StatusCode, T) {
+    send_to(DisassemblerRegistry::builtin(), request).await
+}
+
+/// Sends `request` to a fresh service instance serving `registry`.
+async fn send_to<T: DeserializeOwned>(
+    registry: DisassemblerRegistry,
+    request: Request<Body>,
+) -> (StatusCode, T) {
+    let resp = router(Arc::new(registry)).oneshot(request).await.unwrap();
+
+    let status = resp.status();
+    let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+async fn post<T: DeserializeOwned>(endpoint: &str, payload: &impl Serialize) -> (StatusCode, T) {
+    let request = Request::post(endpoint)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_vec(payload).unwrap()))
+        .unwrap();
+
+    send(request).await
+}
+
+#[tokio::test]
+async fn test_mos6502() {
+    let expected = [
+        "0000 A9 BD    LDA #$BD",
+        "0002 A0 BD    LDY #$BD",
+        "0004 20 28 BA JSR $BA28",
+    ];
+
+    assert_eq!(
+        expected.as_slice(),
+        test_mos6502_impl(MOS6502_TEST_BYTES).await
+    );
+
+    let bytes = tokio::fs::read("test-bin/mos6502/test1.bin").await.unwrap();
+
+    let expected = tokio::fs::read_to_string("test-bin/mos6502/test1.out")
+        .await
+        .unwrap();
+    let expected = expected.lines().collect::<Vec<_>>();
+
+    assert_eq!(expected, test_mos6502_impl(&bytes).await);
+
+    let bytes = tokio::fs::read("test-bin/mos6502/test2.bin").await.unwrap();
+
+    let expected = tokio::fs::read_to_string("test-bin/mos6502/test2.out")
+        .await
+        .unwrap();
+    let expected = expected.lines().collect::<Vec<_>>();
+
+    assert_eq!(expected.as_slice(), test_mos6502_impl(&bytes).await);
+}
+
+async fn test_mos6502_impl(bytes: &[u8]) -> Vec<String> {
+    let payload = Payload::new(
+        bytes.to_vec(),
+        ArchOptions::default().with_width(BitWidth::Bit8),
+        AssemblerOutput::default(),
+    );
+
+    let (status, resp) = post(MOS6502_ENDPOINT, &payload).await;
+    assert_eq!(status, StatusCode::OK);
+    resp
+}
+
+#[tokio::test]
+async fn test_mos6502_offset() {
+    let payload = Payload::new(
+        MOS6502_TEST_BYTES.to_vec(),
+        ArchOptions::default().with_width(BitWidth::Bit8),
+        AssemblerOutput::default().with_addresses(ShowAddress::Start(0xA)),
+    );
+
+    let (_, resp): (_, Vec<String>) = post(MOS6502_ENDPOINT, &payload).await;
+
+    let expected = [
+        "000A A9 BD    LDA #$BD",
+        "000C A0 BD    LDY #$BD",
+        "000E 20 28 BA JSR $BA28",
+    ];
+    assert_eq!(expected.as_slice(), resp);
+}
+
+#[tokio::test]
+async fn test_mos6502_byte_offset() {
+    let payload = Payload::new(
+        MOS6502_TEST_BYTES.to_vec(),
+        ArchOptions::default().with_width(BitWidth::Bit8),
+        AssemblerOutput::default().with_addresses(ShowAddress::Start(0xA)),
+    );
+
+    let (_, resp): (_, Vec<String>) = post(MOS6502_ENDPOINT, &payload).await;
+
+    let expected = [
+        "000A A9 BD    LDA #$BD",
+        "000C A0 BD    LDY #$BD",
+        "000E 20 28 BA JSR $BA28",
+    ];
+    assert_eq!(expected.as_slice(), resp);
+}
+
+#[tokio::test]
+async fn test_mos6502_no_address() {
+    let payload = Payload::new(
+        MOS6502_TEST_BYTES.to_vec(),
+        ArchOptions::default().with_width(BitWidth::Bit8),
+        AssemblerOutput::default().with_addresses(ShowAddress::None),
+    );
+
+    let (_, resp): (_, Vec<String>) = post(MOS6502_ENDPOINT, &payload).await;
+
+    let expected = ["LDA #$BD", "LDY #$BD", "JSR $BA28"];
+    assert_eq!(expected.as_slice(), resp);
+}
+
+#[tokio::test]
+async fn test_mos6502_no_address_lowercase() {
+    let payload = Payload::new(
+        MOS6502_TEST_BYTES.to_vec(),
+        ArchOptions::default().with_width(BitWidth::Bit8),
+        AssemblerOutput::default()
+            .with_addresses(ShowAddress::None)
+            .with_upper_case(false),
+    );
+
+    let (_, resp): (_, Vec<String>) = post(MOS6502_ENDPOINT, &payload).await;
+
+    let expected = ["lda #$bd", "ldy #$bd", "jsr $ba28"];
+    assert_eq!(expected.as_slice(), resp);
+}
+
+#[tokio::test]
+async fn test_mos6502_unsupported() {
+    let payload = Payload::new(
+        MOS6502_TEST_BYTES.to_vec(),
+        ArchOptions::default().with_width(BitWidth::Bit8),
+        AssemblerOutput::default()
+            .with_cycles(true)
+            .with_symbol_table(HashMap::from([(
+                SymbolInfo::new(0xBA28, Scope::Global),
+                "SUBROUTINE".to_string(),
+            )])),
+    );
+
+    let (status, error): (_, ErrorBody) = post(MOS6502_ENDPOINT, &payload).await;
+
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(error.code, "unsupported_option");
+    assert_eq!(error.field.as_deref(), Some("format.cycles"));
+}
+
+#[tokio::test]
+async fn test_mos6502_with_x86() {
+    let bytes = std::fs::read("test-bin/x86/test.bin").unwrap();
+    let payload = Payload::new(
+        bytes,
+        ArchOptions::default().with_width(BitWidth::Bit8),
+        AssemblerOutput::default().with_stop(0xA),
+    );
+
+    let (_, resp): (_, Vec<String>) = post(MOS6502_ENDPOINT, &payload).await;
+
+    let expected = [
+        "0000 7F",
+        "0001 45 4C    EOR $4C",
+        "0003 46 02    LSR $02",
+        "0005 01 01    ORA ($01,X)",
+        "0007 00       BRK",
+        "0008 00       BRK",
+        "0009 00       BRK",
+        "000A 00       BRK",
+    ];
+    assert_eq!(expected.as_slice(), resp)
+}
+
+#[tokio::test]
+async fn test_x86() {
+    let bytes = tokio::fs::read("test-bin/x86/test.bin").await.unwrap();
+    let format = AssemblerOutput::default().with_stop(0xA);
+
+    let payload = Payload::new(
+        bytes.clone(),
+        ArchOptions::default()
+            .with_width(BitWidth::Bit64)
+            .with_syntax("att"),
+        format.clone(),
+    );
+    let (_, resp): (_, Vec<String>) = post(X86_ENDPOINT, &payload).await;
+
+    let expected = [
+        "0x00000000 JG 0x0000000000000047",
+        "0x00000002 ADD (%RCX),%R8B",
+        "0x00000006 ADD %EAX,(%RAX)",
+        "0x00000008 ADD %AL,(%RAX)",
+    ];
+    assert_eq!(expected.as_slice(), resp);
+
+    let payload = Payload::new(
+        bytes,
+        ArchOptions::default().with_width(BitWidth::Bit64),
+        format,
+    );
+    let (_, resp): (_, Vec<String>) = post(X86_ENDPOINT, &payload).await;
+
+    let expected = [
+        "0x00000000 JG SHORT 0000000000000047h",
+        "0x00000002 ADD R8B,[RCX]",
+        "0x00000006 ADD [RAX],EAX",
+        "0x00000008 ADD [RAX],AL",
+    ];
+    assert_eq!(expected.as_slice(), resp)
+}
+
+#[tokio::test]
+async fn test_unimplemented() {
+    let bytes = tokio::fs::read("test-bin/x86/test.bin").await.unwrap();
+    let payload = Payload::new(
+        bytes,
+        ArchOptions::default().with_width(BitWidth::Bit16),
+        AssemblerOutput::default(),
+    );
+
+    let (status, error): (_, ErrorBody) = post(RISC_V_ENDPOINT, &payload).await;
+
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(error.code, "unimplemented");
+    assert_eq!(error.message, "The implementation has not been done");
+}
+
+#[tokio::test]
+async fn test_disassemble_arch() {
+    let payload = Payload::new(
+        MOS6502_TEST_BYTES.to_vec(),
+        ArchOptions::default(),
+        AssemblerOutput::default().with_addresses(ShowAddress::None),
+    );
+
+    let (_, resp): (_, Vec<String>) = post("/disassemble/mos6502", &payload).await;
+
+    let expected = ["LDA #$BD", "LDY #$BD", "JSR $BA28"];
+    assert_eq!(expected.as_slice(), resp);
+
+    let (status, _): (_, ErrorBody) = post("/disassemble/x86", &payload).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, error): (_, ErrorBody) = post("/disassemble/z80", &payload).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error.code, "unknown_arch");
+    assert_eq!(error.message, "Unknown architecture: z80");
+    assert_eq!(error.allowed, ["mos6502", "riscv", "x86"]);
+}
+
+#[tokio::test]
+async fn test_xrefs() {
+    // LDA $C006; BNE $C000; RTS
+    let bytes = vec![0xad, 0x06, 0xc0, 0xd0, 0xfb, 0x60, 0x00];
+    let payload = Payload::new(
+        bytes,
+        ArchOptions::default(),
+        AssemblerOutput::default()
+            .with_addresses(ShowAddress::Start(0xC000))
+            .with_stop(0xC005)
+            .with_xrefs(true),
+    );
+
+    let (status, listing): (_, Listing) = post(MOS6502_ENDPOINT, &payload).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listing.lines[0], "C000 AD 06 C0 LDA $C006 ; xref: $C003");
+    assert_eq!(
+        listing.xrefs.unwrap(),
+        [
+            Xref {
+                address: 0xC000,
+                from: 0xC003,
+                kind: RefKind::Branch
+            },
+            Xref {
+                address: 0xC006,
+                from: 0xC000,
+                kind: RefKind::Read
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_symbol_file() {
+    let payload = |text: &str| {
+        Payload::new(
+            MOS6502_TEST_BYTES.to_vec(),
+            ArchOptions::default(),
+            AssemblerOutput::default(),
+        )
+        .with_symbols(SymbolFile {
+            format: None,
+            text: text.to_string(),
+        })
+    };
+
+    let (status, error): (_, ErrorBody) = post(
+        MOS6502_ENDPOINT,
+        &payload("al C:0000 .start\nal C:zz .bad\n"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.code, "invalid_symbol_file");
+    assert_eq!(error.field.as_deref(), Some("symbols"));
+    assert_eq!(
+        error.message,
+        "Invalid symbol file line 2: expected `al ADDRESS .NAME`"
+    );
+
+    let (status, lines): (_, Vec<String>) = post(
+        MOS6502_ENDPOINT,
+        &payload("$0000#start#\n$BA28#PrintChar#\n"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        lines,
+        [
+            "start:",
+            "0000 A9 BD    LDA #$BD",
+            "0002 A0 BD    LDY #$BD",
+            "0004 20 28 BA JSR PrintChar",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_detect() {
+    let bytes = std::fs::read("test-bin/x86/xrefs.bin").unwrap();
+    let (status, candidates): (_, Vec<Candidate>) =
+        post(DETECT_ENDPOINT, &DetectPayload::new(bytes)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(candidates[0].arch, "x86");
+    assert_eq!(candidates[0].input, Input::Raw);
+    assert!(candidates
+        .iter()
+        .any(|candidate| candidate.arch == "mos6502"));
+    assert!(candidates
+        .windows(2)
+        .all(|pair| pair[0].confidence >= pair[1].confidence));
+
+    #[cfg(feature = "elf")]
+    {
+        let bytes = std::fs::read("test-bin/x86/elf64.bin").unwrap();
+        let (_, candidates): (_, Vec<Candidate>) =
+            post(DETECT_ENDPOINT, &DetectPayload::new(bytes)).await;
+        assert_eq!(
+            candidates,
+            [Candidate {
+                arch: "x86".to_string(),
+                width: Some(BitWidth::Bit64),
+                confidence: 1.0,
+                input: Input::Elf,
+            }]
+        );
+    }
+}
+
+#[cfg(feature = "elf")]
+#[tokio::test]
+async fn test_elf() {
+    let mut bytes = std::fs::read("test-bin/x86/elf64.bin").unwrap();
+    let payload = |bytes: &[u8]| {
+        Payload::new(
+            bytes.to_vec(),
+            ArchOptions::default(),
+            AssemblerOutput::default(),
+        )
+        .with_input(Input::Elf)
+    };
+
+    let (status, lines): (_, Vec<String>) = post(DISASSEMBLE_ENDPOINT, &payload(&bytes)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        lines,
+        [
+            "; section .text at 0x400078, 19 bytes",
+            "_start:",
+            "0x00400078 CALL helper",
+            "0x0040007D MOV EAX,[counter]",
+            "0x00400083 RET",
+            "helper:",
+            "0x00400084 INC DWORD PTR [counter]",
+            "0x0040008A RET",
+        ]
+    );
+
+    let (status, error): (_, ErrorBody) = post(X86_ENDPOINT, &payload(&bytes)).await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(error.field.as_deref(), Some("input"));
+
+    // e_machine of ARM
+    bytes[0x12] = 40;
+    let (status, error): (_, ErrorBody) = post(DISASSEMBLE_ENDPOINT, &payload(&bytes)).await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(error.code, "unsupported_machine");
+    assert_eq!(error.message, "Unsupported machine: ARM");
+
+    // e_machine of RISC-V in a 64-bit file
+    bytes[0x12] = 243;
+    let (status, error): (_, ErrorBody) = post(DISASSEMBLE_ENDPOINT, &payload(&bytes)).await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(error.code, "unsupported_machine");
+    assert_eq!(error.message, "Unsupported machine: RISCV 64-bit");
+}
+
+#[cfg(feature = "nes")]
+#[tokio::test]
+async fn test_nes() {
+    // NROM-128 with the reset handler at 0xC000 and the other vectors at 0xC004
+    let mut rom = b"NES\x1A\x01\x00\x00\x00".to_vec();
+    rom.resize(16, 0);
+    let mut prg = vec![0; 0x4000];
+    prg[..5].copy_from_slice(&[0x4C, 0x00, 0xC0, 0x00, 0x40]);
+    prg[0x3FFA..].copy_from_slice(&[0x04, 0xC0, 0x00, 0xC0, 0x04, 0xC0]);
+    rom.extend(prg);
+    let payload = Payload::new(
+        rom.clone(),
+        ArchOptions::default(),
+        AssemblerOutput::default().with_discovery(Discovery::Recursive(vec![])),
+    )
+    .with_input(Input::Nes);
+
+    let (status, lines): (_, Vec<String>) = post(DISASSEMBLE_ENDPOINT, &payload).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        lines[..6],
+        [
+            "; iNES mapper 0 (NROM), 1 PRG-ROM banks",
+            "; NMI 0xC004, RESET 0xC000, IRQ 0xC004",
+            "; section PRG bank 0 at 0xC000, 16384 bytes",
+            "C000 4C 00 C0 JMP $C000",
+            "C003          .BYTE $00",
+            "C004 40       RTI",
+        ]
+    );
+
+    // MMC3
+    rom[6] = 0x40;
+    let payload = Payload::new(rom, ArchOptions::default(), AssemblerOutput::default())
+        .with_input(Input::Nes);
+    let (status, error): (_, ErrorBody) = post(DISASSEMBLE_ENDPOINT, &payload).await;
+    assert_eq!(status, StatusCode::NOT_IMPLEMENTED);
+    assert_eq!(error.code, "unsupported_mapper");
+    assert_eq!(error.allowed, ["0", "1", "2", "3", "7"]);
+}
+
+#[cfg(feature = "hex")]
+#[tokio::test]
+async fn test_hex() {
+    let payload = |text: &str| {
+        Payload::new(
+            text.as_bytes().to_vec(),
+            ArchOptions::default().with_arch("mos6502"),
+            AssemblerOutput::default(),
+        )
+        .with_input(Input::Hex)
+    };
+
+    let (status, lines): (_, Vec<String>) = post(
+        DISASSEMBLE_ENDPOINT,
+        &payload(":01C0000060DF\n:01C01000EA45\n:00000001FF\n"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        lines,
+        [
+            "; section Intel HEX at 0xC000, 1 bytes",
+            "C000 60       RTS",
+            "; gap at 0xC001, 15 bytes",
+            "; section Intel HEX at 0xC010, 1 bytes",
+            "C010 EA       NOP",
+        ]
+    );
+
+    let (status, error): (_, ErrorBody) = post(
+        DISASSEMBLE_ENDPOINT,
+        &payload(":01C0000060DF\n:01C01000EA46\n"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.code, "invalid_record");
+    assert_eq!(error.message, "Invalid record at line 2: checksum mismatch");
+}
+
+#[cfg(feature = "pe")]
+#[tokio::test]
+async fn test_pe() {
+    let bytes = std::fs::read("test-bin/x86/pe64.bin").unwrap();
+    let payload = |bytes: &[u8]| {
+        Payload::new(
+            bytes.to_vec(),
+            ArchOptions::default(),
+            AssemblerOutput::default().with_discovery(Discovery::Recursive(vec![])),
+        )
+        .with_input(Input::Pe)
+    };
+
+    let (status, lines): (_, Vec<String>) = post(DISASSEMBLE_ENDPOINT, &payload(&bytes)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        lines[..5],
+        [
+            "; section .text at 0x140001000, 48 bytes",
+            "entry:",
+            "0x140001000 SUB RSP,28h",
+            "0x140001004 CALL helper",
+            "0x140001009 CALL QWORD PTR [KERNEL32.dll!CreateFileW]",
+        ]
+    );
+
+    let (status, error): (_, ErrorBody) =
+        post(DISASSEMBLE_ENDPOINT, &payload(&bytes[..0x100])).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.code, "malformed_input");
+    assert_eq!(error.field.as_deref(), Some("bytes"));
+}
+
+#[tokio::test]
+async fn test_cfg() {
+    // LDX #$00; loop: JSR $C00A; DEX; BNE loop; RTS; BRK; RTS
+    let bytes = vec![
+        0xa2, 0x00, 0x20, 0x0a, 0xc0, 0xca, 0xd0, 0xfa, 0x60, 0x00, 0x60,
+    ];
+    let payload = |graph| {
+        CfgPayload::new(
+            Payload::new(
+                bytes.clone(),
+                ArchOptions::default().with_arch("mos6502"),
+                AssemblerOutput::default().with_addresses(ShowAddress::Start(0xC000)),
+            ),
+            0xC000,
+            graph,
+        )
+    };
+
+    let (status, cfg): (_, Cfg) = post(CFG_ENDPOINT, &payload(GraphFormat::Json)).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let blocks: Vec<_> = cfg
+        .blocks
+        .iter()
+        .map(|block| (block.start, block.end))
+        .collect();
+    assert_eq!(
+        blocks,
+        [
+            (0xC000, 0xC002),
+            (0xC002, 0xC005),
+            (0xC005, 0xC008),
+            (0xC008, 0xC009)
+        ]
+    );
+    let edges: Vec<_> = cfg
+        .edges
+        .iter()
+        .map(|edge| (edge.from, edge.to, edge.kind))
+        .collect();
+    assert_eq!(
+        edges,
+        [
+            (0xC000, Some(0xC002), EdgeKind::FallThrough),
+            (0xC002, Some(0xC00A), EdgeKind::Call),
+            (0xC002, Some(0xC005), EdgeKind::FallThrough),
+            (0xC005, Some(0xC002), EdgeKind::Taken),
+            (0xC005, Some(0xC008), EdgeKind::FallThrough),
+        ]
+    );
+
+    let request = Request::post(CFG_ENDPOINT)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            serde_json::to_vec(&payload(GraphFormat::Dot)).unwrap(),
+        ))
+        .unwrap();
+    let resp = router(Arc::new(DisassemblerRegistry::builtin()))
+        .oneshot(request)
+        .await
+        .unwrap();
+    assert_eq!(resp.headers()[CONTENT_TYPE], "text/vnd.graphviz");
+    let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let dot = String::from_utf8(body.to_vec()).unwrap();
+    assert!(dot.contains(r#""C005" [label="C005 DEX\lC006 BNE $C002\l"];"#));
+    assert!(dot.contains(r#""C005" -> "C002" [label="taken"];"#));
+}
+
+#[tokio::test]
+async fn test_functions() {
+    // LDX #$00; loop: JSR $C00A; DEX; BNE loop; RTS; BRK; RTS
+    let bytes = vec![
+        0xa2, 0x00, 0x20, 0x0a, 0xc0, 0xca, 0xd0, 0xfa, 0x60, 0x00, 0x60,
+    ];
+    let payload = FunctionsPayload::new(
+        Payload::new(
+            bytes,
+            ArchOptions::default().with_arch("mos6502"),
+            AssemblerOutput::default().with_addresses(ShowAddress::Start(0xC000)),
+        ),
+        vec![],
+    );
+
+    let (status, graph): (_, CallGraph) = post(FUNCTIONS_ENDPOINT, &payload).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        graph.functions,
+        [
+            Function {
+                start: 0xC000,
+                end: 0xC009,
+                size: 9,
+                leaf: false
+            },
+            Function {
+                start: 0xC00A,
+                end: 0xC00B,
+                size: 1,
+                leaf: true
+            },
+        ]
+    );
+    assert_eq!(
+        graph.calls,
+        [Call {
+            caller: 0xC000,
+            callee: 0xC00A,
+            from: 0xC002
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_disassemble_unified() {
+    let bytes = tokio::fs::read("test-bin/x86/test.bin").await.unwrap();
+    let payload = |arch| {
+        Payload::new(
+            bytes.clone(),
+            arch,
+            AssemblerOutput::default().with_stop(0xA),
+        )
+    };
+
+    let (_, resp): (_, Vec<String>) = post(
+        DISASSEMBLE_ENDPOINT,
+        &payload(
+            ArchOptions::default()
+                .with_arch("x86")
+                .with_width(BitWidth::Bit64),
+        ),
+    )
+    .await;
+
+    let expected = [
+        "0x00000000 JG SHORT 0000000000000047h",
+        "0x00000002 ADD R8B,[RCX]",
+        "0x00000006 ADD [RAX],EAX",
+        "0x00000008 ADD [RAX],AL",
+    ];
+    assert_eq!(expected.as_slice(), resp);
+
+    let (status, error): (_, ErrorBody) = post(
+        DISASSEMBLE_ENDPOINT,
+        &payload(
+            ArchOptions::default()
+                .with_arch("x86")
+                .with_width(BitWidth::Bit8),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.code, "invalid_bit_width");
+    assert_eq!(error.field.as_deref(), Some("arch.width"));
+    assert_eq!(error.allowed, ["16", "32", "64"]);
+
+    let (status, error): (_, ErrorBody) = post(
+        DISASSEMBLE_ENDPOINT,
+        &payload(ArchOptions::default().with_arch("riscv")),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.code, "missing_option");
+    assert_eq!(error.field.as_deref(), Some("arch.width"));
+
+    let (status, error): (_, ErrorBody) = post(
+        DISASSEMBLE_ENDPOINT,
+        &payload(ArchOptions::default().with_width(BitWidth::Bit64)),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.field.as_deref(), Some("arch.arch"));
+}
+
+#[tokio::test]
+async fn test_errors() {
+    let payload = Payload::new(
+        MOS6502_TEST_BYTES.to_vec(),
+        ArchOptions::default()
+            .with_width(BitWidth::Bit64)
+            .with_syntax("masm"),
+        AssemblerOutput::default(),
+    );
+
+    let (status, error): (_, ErrorBody) = post(X86_ENDPOINT, &payload).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.code, "unsupported_syntax");
+    assert_eq!(error.field.as_deref(), Some("arch.syntax"));
+    assert_eq!(error.allowed, ["intel", "att", "at&t"]);
+
+    let request = Request::post(X86_ENDPOINT)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from("{"))
+        .unwrap();
+    let (status, error): (_, ErrorBody) = send(request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.code, "invalid_payload");
+
+    let request = Request::post(X86_ENDPOINT).body(Body::from("{}")).unwrap();
+    let (status, error): (_, ErrorBody) = send(request).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(error.code, "invalid_payload");
+
+    let request = Request::get("/z80").body(Body::empty()).unwrap();
+    let (status, error): (_, ErrorBody) = send(request).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error.code, "not_found");
+}
+
+#[tokio::test]
+async fn test_architectures() {
+    let request = Request::get(ARCHITECTURES_ENDPOINT)
+        .body(Body::empty())
+        .unwrap();
+    let (_, resp): (_, Vec<Architecture>) = send(request).await;
+    let find = |name: &str| {
+        resp.iter()
+            .find(|arch| arch.capabilities.name == name)
+            .unwrap()
+    };
+
+    let mos6502 = find("mos6502");
+    assert_eq!(mos6502.endpoint, "/disassemble/mos6502");
+    assert_eq!(mos6502.capabilities, mos6502::Mos6502::capabilities());
+    assert!(!mos6502.capabilities.width_required);
+
+    let x86 = find("x86");
+    assert_eq!(x86.endpoint, "/disassemble/x86");
+    assert!(x86.capabilities.width_required);
+    assert_eq!(
+        x86.capabilities.widths,
+        [BitWidth::Bit16, BitWidth::Bit32, BitWidth::Bit64]
+    );
+    assert_eq!(x86.capabilities.syntaxes[0].name, "intel");
+    assert_eq!(x86.capabilities.syntaxes[1].aliases, ["at&t"]);
+
+    let risc_v = find("riscv");
+    assert_eq!(risc_v.endpoint, "/disassemble/riscv");
+    assert!(!risc_v.capabilities.implemented);
+
+    // only the registered disassemblers are listed
+    let registry = DisassemblerRegistry::default().with::<mos6502::Mos6502>();
+    let request = Request::get(ARCHITECTURES_ENDPOINT)
+        .body(Body::empty())
+        .unwrap();
+    let (_, resp): (_, Vec<Architecture>) = send_to(registry, request).await;
+    assert_eq!(resp.len(), 1);
+    assert_eq!(resp[0].capabilities.name, "mos6502");
+}
+
+#[tokio::test]
+async fn test_diff() {
+    let payload = |bytes: &[u8], patched: &[u8]| {
+        DiffPayload::new(
+            Payload::new(
+                bytes.to_vec(),
+                ArchOptions::default().with_arch("mos6502"),
+                AssemblerOutput::default().with_addresses(ShowAddress::Start(0xC000)),
+            ),
+            patched.to_vec(),
+        )
+    };
+    // a NOP inserted before the subroutine shifts the JSR target
+    let old = [0x20, 0x06, 0xC0, 0xA9, 0x01, 0x60, 0xEA, 0x60];
+    let new = [0x20, 0x07, 0xC0, 0xA9, 0x01, 0xEA, 0x60, 0xEA, 0x60];
+
+    let (status, lines): (_, Vec<String>) = post(DIFF_ENDPOINT, &payload(&old, &new)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        lines,
+        [
+            "--- old",
+            "+++ new",
+            "@@ -C000,5 +C000,6 @@",
+            " C000 JSR $C007",
+            " C003 LDA #$01",
+            "+C005 NOP",
+            " C006 RTS",
+            " C007 NOP",
+            " C008 RTS",
+        ]
+    );
+
+    let (_, lines): (_, Vec<String>) = post(DIFF_ENDPOINT, &payload(&old, &old)).await;
+    assert!(lines.is_empty());
+}
+
+#[tokio::test]
+async fn test_search() {
+    let payload = |pattern: &str| {
+        SearchPayload::new(
+            Payload::new(
+                std::fs::read("test-bin/x86/xrefs.bin").unwrap(),
+                ArchOptions::default()
+                    .with_arch("x86")
+                    .with_width(BitWidth::Bit32),
+                AssemblerOutput::default().with_addresses(ShowAddress::Start(0x1000)),
+            ),
+            pattern,
+        )
+        .with_context(1)
+    };
+
+    let (status, matches): (_, Vec<Match>) = post(SEARCH_ENDPOINT, &payload("call <mem>")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        matches,
+        [Match {
+            address: 0x100A,
+            lines: vec![
+                " 1005 MOV [2004h],EAX".to_string(),
+                ">100A CALL DWORD PTR [3000h]".to_string(),
+                " 1010 JE SHORT 0".to_string(),
+            ],
+        }]
+    );
+
+    let (_, matches): (_, Vec<Match>) = post(
+        SEARCH_ENDPOINT,
+        &payload("mov <reg>, <mem>; mov <mem>, <reg>"),
+    )
+    .await;
+    assert_eq!(matches.len(), 1);
+    assert_eq!(matches[0].address, 0x1000);
+
+    let (status, error): (_, ErrorBody) = post(SEARCH_ENDPOINT, &payload("call <far>")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error.code, "invalid_pattern");
+    assert_eq!(error.field.as_deref(), Some("pattern"));
+    assert_eq!(error.allowed, ["<imm>", "<mem>", "<target>", "<reg>"]);
+}